@@ -1,8 +1,14 @@
+use std::env;
+use std::fmt;
+use std::fs;
 use std::io;
 use std::io::Read;
+use std::io::Write;
+use std::process;
+use std::rc::Rc;
+use std::time::Instant;
 
 fn parse_bottom_line(s: &str) -> Option<usize> {
-    dbg!(s);
     match s.trim().rsplit_once(' ') {
         Some((_, s2)) => s2.parse::<usize>().ok(),
         None => None,
@@ -14,7 +20,7 @@ fn parse_containers(input: &str, nstacks: usize) -> Vec<u8> {
     let mut s = input;
     loop {
         if s.starts_with("   ") {
-            r.push(' ' as u8);
+            r.push(b' ');
             if s.len() >= 4 {
                 s = &s[4..];
             } else {
@@ -33,7 +39,527 @@ fn parse_containers(input: &str, nstacks: usize) -> Vec<u8> {
     r
 }
 
+// A contiguous run of crates, shared between stacks so that splitting it does
+// not copy anything. With `reversed` set the run is read from the back, which
+// makes reversing a whole bulk move cheap.
+#[derive(Clone)]
+struct Segment {
+    data: Rc<[u8]>,
+    start: usize,
+    end: usize,
+    reversed: bool,
+}
+
+// segments at most this long are merged when they end up next to each other,
+// so that many small moves don't fragment a stack into single crates
+const MERGE_LIMIT: usize = 32;
+
+impl Segment {
+    fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    // index 0 is the bottom crate
+    fn get(&self, i: usize) -> u8 {
+        if self.reversed {
+            self.data[self.end - 1 - i]
+        } else {
+            self.data[self.start + i]
+        }
+    }
+
+    fn split_top(&mut self, n: usize) -> Segment {
+        let mut top = self.clone();
+        if self.reversed {
+            top.end = self.start + n;
+            self.start += n;
+        } else {
+            top.start = self.end - n;
+            self.end -= n;
+        }
+        top
+    }
+}
+
+#[derive(Clone, Default)]
+struct Stack {
+    segments: Vec<Segment>,
+    len: usize,
+}
+
+impl Stack {
+    fn from_bytes(b: &[u8]) -> Stack {
+        let mut s = Stack::default();
+        if !b.is_empty() {
+            s.push_segment(Segment {
+                data: Rc::from(b),
+                start: 0,
+                end: b.len(),
+                reversed: false,
+            });
+        }
+        s
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn top(&self) -> Option<u8> {
+        let seg = self.segments.last()?;
+        Some(seg.get(seg.len() - 1))
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let mut r = Vec::with_capacity(self.len);
+        for seg in &self.segments {
+            r.extend((0..seg.len()).map(|i| seg.get(i)));
+        }
+        r
+    }
+
+    fn push_segment(&mut self, seg: Segment) {
+        self.len += seg.len();
+        if let Some(last) = self.segments.last_mut() {
+            if last.len() + seg.len() <= MERGE_LIMIT {
+                let mut merged: Vec<u8> = (0..last.len()).map(|i| last.get(i)).collect();
+                merged.extend((0..seg.len()).map(|i| seg.get(i)));
+                *last = Segment {
+                    end: merged.len(),
+                    data: Rc::from(merged),
+                    start: 0,
+                    reversed: false,
+                };
+                return;
+            }
+        }
+        self.segments.push(seg);
+    }
+
+    // removes the top `n` crates, which must not be more than the height
+    fn split_top(&mut self, mut n: usize) -> Stack {
+        let mut top = Vec::new();
+        self.len -= n;
+        while n > 0 {
+            let last = self.segments.last_mut().expect("stack underflow");
+            if last.len() <= n {
+                n -= last.len();
+                top.push(self.segments.pop().unwrap());
+            } else {
+                top.push(last.split_top(n));
+                n = 0;
+            }
+        }
+        top.reverse();
+        let len = top.iter().map(|s| s.len()).sum();
+        Stack { segments: top, len }
+    }
+
+    fn append(&mut self, other: Stack) {
+        for seg in other.segments {
+            self.push_segment(seg);
+        }
+    }
+
+    fn reverse(&mut self) {
+        self.segments.reverse();
+        for seg in &mut self.segments {
+            seg.reversed = !seg.reversed;
+        }
+    }
+}
+
+fn render_stacks(stacks: &[Stack]) -> String {
+    let stacks: Vec<Vec<u8>> = stacks.iter().map(|s| s.bytes()).collect();
+    let height = stacks.iter().map(|s| s.len()).max().unwrap_or(0);
+    let mut out = String::new();
+    for level in (0..height).rev() {
+        let cells: Vec<String> = stacks
+            .iter()
+            .map(|s| match s.get(level) {
+                Some(c) => format!("[{}]", char::from(*c)),
+                None => String::from("   "),
+            })
+            .collect();
+        out.push_str(&cells.join(" "));
+        out.push('\n');
+    }
+    let labels: Vec<String> = (1..=stacks.len()).map(|i| format!("{:^3}", i)).collect();
+    out.push_str(&labels.join(" "));
+    out.push('\n');
+    out
+}
+
+struct Move {
+    line: usize,
+    count: usize,
+    from: usize,
+    to: usize,
+}
+
+#[derive(Debug)]
+enum MoveError {
+    Parse {
+        line: usize,
+        descr: &'static str,
+        input: String,
+    },
+    BadStack {
+        line: usize,
+        index: usize,
+        nstacks: usize,
+        heights: Vec<usize>,
+    },
+    Underflow {
+        line: usize,
+        count: usize,
+        from: usize,
+        heights: Vec<usize>,
+    },
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::Parse { line, descr, input } => {
+                write!(f, "line {line}: {descr} (input: {input})")
+            }
+            MoveError::BadStack {
+                line,
+                index,
+                nstacks,
+                heights,
+            } => write!(
+                f,
+                "line {line}: stack {index} does not exist (stacks are 1 to {nstacks}, stack heights: {heights:?})"
+            ),
+            MoveError::Underflow {
+                line,
+                count,
+                from,
+                heights,
+            } => write!(
+                f,
+                "line {line}: cannot move {count} crates from stack {from} (stack heights: {heights:?})"
+            ),
+        }
+    }
+}
+
+fn parse_move(l: &str, line: usize) -> Result<Move, MoveError> {
+    let err = |descr| MoveError::Parse {
+        line,
+        descr,
+        input: String::from(l),
+    };
+    let (count_str, rest) = l
+        .strip_prefix("move ")
+        .ok_or_else(|| err("no move prefix"))?
+        .split_once(" from ")
+        .ok_or_else(|| err("no from in line"))?;
+    let (from_str, to_str) = rest.split_once(" to ").ok_or_else(|| err("no to in line"))?;
+    Ok(Move {
+        line,
+        count: count_str.parse().map_err(|_| err("failed to parse count"))?,
+        from: from_str.parse().map_err(|_| err("failed to parse from"))?,
+        to: to_str.trim().parse().map_err(|_| err("failed to parse to"))?,
+    })
+}
+
+fn check_stack(stacks: &[Stack], index: usize, line: usize) -> Result<usize, MoveError> {
+    if index == 0 || index > stacks.len() {
+        return Err(MoveError::BadStack {
+            line,
+            index,
+            nstacks: stacks.len(),
+            heights: stacks.iter().map(|s| s.len()).collect(),
+        });
+    }
+    Ok(index - 1)
+}
+
+#[derive(Clone, Copy)]
+enum Crane {
+    // moves crates one at a time, so a bulk move reverses their order
+    Mover9000,
+    // moves several crates at once, keeping their order
+    Mover9001,
+}
+
+// In lenient mode an underflowing move is clamped to the height of its source
+// stack and the error is returned as a warning instead. The clamped count is
+// written back to the move so that it can be undone exactly.
+fn apply_move(
+    stacks: &mut [Stack],
+    mv: &mut Move,
+    crane: Crane,
+    lenient: bool,
+) -> Result<Option<MoveError>, MoveError> {
+    let from = check_stack(stacks, mv.from, mv.line)?;
+    let to = check_stack(stacks, mv.to, mv.line)?;
+    let len = stacks[from].len();
+    let mut warning = None;
+    if mv.count > len {
+        let e = MoveError::Underflow {
+            line: mv.line,
+            count: mv.count,
+            from: mv.from,
+            heights: stacks.iter().map(|s| s.len()).collect(),
+        };
+        if !lenient {
+            return Err(e);
+        }
+        warning = Some(e);
+        mv.count = len;
+    }
+    let mut rem = stacks[from].split_top(mv.count);
+    if let Crane::Mover9000 = crane {
+        rem.reverse();
+    }
+    stacks[to].append(rem);
+    Ok(warning)
+}
+
+// Keeps the stacks together with the move log and the number of moves that
+// have been applied so far, so that moves can be stepped in both directions.
+struct Simulation {
+    stacks: Vec<Stack>,
+    moves: Vec<Move>,
+    done: usize,
+    crane: Crane,
+    lenient: bool,
+}
+
+impl Simulation {
+    fn redo(&mut self) -> Option<Result<Option<MoveError>, MoveError>> {
+        let mv = self.moves.get_mut(self.done)?;
+        let res = apply_move(&mut self.stacks, mv, self.crane, self.lenient);
+        if res.is_ok() {
+            self.done += 1;
+        }
+        Some(res)
+    }
+
+    fn undo(&mut self) -> Option<Result<Option<MoveError>, MoveError>> {
+        let mv = &mut self.moves[self.done.checked_sub(1)?];
+        // undoing a move is the same move in the other direction, with the
+        // same crane model
+        let mut inverse = Move {
+            line: mv.line,
+            count: mv.count,
+            from: mv.to,
+            to: mv.from,
+        };
+        let res = apply_move(&mut self.stacks, &mut inverse, self.crane, self.lenient);
+        mv.count = inverse.count;
+        if res.is_ok() {
+            self.done -= 1;
+        }
+        Some(res)
+    }
+}
+
+struct Trace {
+    every: usize,
+    out: Box<dyn Write>,
+}
+
+impl Trace {
+    fn record(&mut self, title: &str, stacks: &[Stack]) {
+        writeln!(self.out, "{title}\n{}", render_stacks(stacks)).expect("failed to write trace");
+    }
+}
+
+struct Bench {
+    stacks: usize,
+    height: usize,
+    moves: usize,
+}
+
+struct Options {
+    trace: Option<Trace>,
+    bench: Option<Bench>,
+    lenient: bool,
+    crane: Crane,
+    reverse: bool,
+}
+
+impl Options {
+    fn from_args() -> Options {
+        let mut trace = false;
+        let mut every = 1;
+        let mut path = None;
+        let mut lenient = false;
+        let mut crane = Crane::Mover9001;
+        let mut reverse = false;
+        let mut bench = None;
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--trace" => trace = true,
+                "--trace-every" => {
+                    trace = true;
+                    every = args
+                        .next()
+                        .expect("--trace-every needs a value")
+                        .parse()
+                        .expect("failed to parse --trace-every");
+                    if every == 0 {
+                        panic!("--trace-every must be at least 1");
+                    }
+                }
+                "--trace-out" => {
+                    trace = true;
+                    path = Some(args.next().expect("--trace-out needs a path"));
+                }
+                "--lenient" => lenient = true,
+                "--crane" => {
+                    crane = match args.next().expect("--crane needs a model").as_str() {
+                        "9000" => Crane::Mover9000,
+                        "9001" => Crane::Mover9001,
+                        m => panic!("unknown crane model {m}"),
+                    }
+                }
+                "--reverse" => reverse = true,
+                "--bench" => {
+                    bench = Some(Bench {
+                        stacks: 9,
+                        height: 10000,
+                        moves: 100000,
+                    })
+                }
+                "--bench-stacks" | "--bench-height" | "--bench-moves" => {
+                    let b = bench.as_mut().expect("--bench must come before its parameters");
+                    let v = args
+                        .next()
+                        .unwrap_or_else(|| panic!("{arg} needs a value"))
+                        .parse()
+                        .unwrap_or_else(|_| panic!("failed to parse {arg}"));
+                    match arg.as_str() {
+                        "--bench-stacks" => b.stacks = v,
+                        "--bench-height" => b.height = v,
+                        _ => b.moves = v,
+                    }
+                }
+                _ => panic!("unknown argument {arg}"),
+            }
+        }
+        let trace = if trace {
+            let out: Box<dyn Write> = match path {
+                Some(p) => Box::new(fs::File::create(p).expect("failed to create trace file")),
+                None => Box::new(io::stderr()),
+            };
+            Some(Trace { every, out })
+        } else {
+            None
+        };
+        Options {
+            trace,
+            bench,
+            lenient,
+            crane,
+            reverse,
+        }
+    }
+}
+
+// xorshift64, good enough to generate synthetic benchmark input
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
+
+fn generate(b: &Bench) -> (Vec<Vec<u8>>, Vec<Move>) {
+    let mut rng = Rng(0x2545f4914f6cdd1d);
+    let stacks: Vec<Vec<u8>> = (0..b.stacks)
+        .map(|_| (0..b.height).map(|_| b'A' + rng.next(26) as u8).collect())
+        .collect();
+    let mut heights = vec![b.height; b.stacks];
+    let mut moves = Vec::with_capacity(b.moves);
+    for line in 0..b.moves {
+        let from = rng.next(b.stacks);
+        let to = rng.next(b.stacks);
+        let count = rng.next(heights[from] + 1);
+        heights[from] -= count;
+        heights[to] += count;
+        moves.push(Move {
+            line,
+            count,
+            from: from + 1,
+            to: to + 1,
+        });
+    }
+    (stacks, moves)
+}
+
+// the previous implementation, which copies every moved crate
+fn run_strings(stacks: &[Vec<u8>], moves: &[Move], crane: Crane) -> Vec<Vec<u8>> {
+    let mut stacks: Vec<String> = stacks
+        .iter()
+        .map(|s| String::from_utf8(s.clone()).unwrap())
+        .collect();
+    for mv in moves {
+        let len = stacks[mv.from - 1].len();
+        let mut rem: String = stacks[mv.from - 1].drain(len - mv.count..).collect();
+        if let Crane::Mover9000 = crane {
+            rem = rem.chars().rev().collect();
+        }
+        stacks[mv.to - 1].push_str(&rem);
+    }
+    stacks.into_iter().map(|s| s.into_bytes()).collect()
+}
+
+fn run_segments(stacks: &[Vec<u8>], moves: &mut [Move], crane: Crane) -> Vec<Vec<u8>> {
+    let mut stacks: Vec<Stack> = stacks.iter().map(|s| Stack::from_bytes(s)).collect();
+    for mv in moves {
+        apply_move(&mut stacks, mv, crane, false).expect("generated move is invalid");
+    }
+    stacks.iter().map(|s| s.bytes()).collect()
+}
+
+fn run_bench(b: &Bench, crane: Crane) {
+    if b.stacks == 0 {
+        panic!("--bench-stacks must be at least 1");
+    }
+    let (stacks, mut moves) = generate(b);
+    let moved: usize = moves.iter().map(|mv| mv.count).sum();
+    println!(
+        "{} stacks, {} crates each, {} moves, {} crates moved",
+        b.stacks, b.height, b.moves, moved
+    );
+
+    let start = Instant::now();
+    let expected = run_strings(&stacks, &moves, crane);
+    let t_strings = start.elapsed();
+    println!("strings:  {:?}", t_strings);
+
+    let start = Instant::now();
+    let got = run_segments(&stacks, &mut moves, crane);
+    let t_segments = start.elapsed();
+    println!("segments: {:?}", t_segments);
+
+    if got != expected {
+        panic!("segment stacks disagree with string stacks");
+    }
+    println!(
+        "speedup:  {:.1}x",
+        t_strings.as_secs_f64() / t_segments.as_secs_f64()
+    );
+}
+
 fn main() {
+    let mut opts = Options::from_args();
+    if let Some(b) = &opts.bench {
+        run_bench(b, opts.crane);
+        return;
+    }
+
     let mut input = String::new();
     io::stdin().read_to_string(&mut input).expect("failed to read stdin");
 
@@ -42,51 +568,83 @@ fn main() {
         panic!("invalid input format");
     }
 
-    //dbg!(parts[0]);
-
     let mut lines = parts[0].lines().rev();
     let nstacks = parse_bottom_line(lines.next().expect("no bottom line in first section")).expect("failed to parse bottom line");
-    let mut stacks = vec![String::from(""); nstacks];
+    let mut stacks = vec![Vec::new(); nstacks];
     for l in lines {
         let containers = parse_containers(l, nstacks);
         for (i, u) in containers.iter().enumerate() {
-            let c = char::from(*u);
-            if c != ' ' {
-                stacks[i].push(c);
+            if *u != b' ' {
+                stacks[i].push(*u);
             }
         }
     }
-    dbg!(&stacks);
-
-    for l in parts[1].lines() {
-        let count_str = l.strip_prefix("move ")
-            .expect("no move prefix")
-            .split_once(" ")
-            .expect("no space after move")
-            .0;
-        let count : usize = count_str.parse().expect("failed to parse count");
-        //dbg!(count);
-        let from_str = l.split_once("from ")
-            .expect("no from in line")
-            .1
-            .split_once(" ")
-            .expect("no space after from")
-            .0;
-        let from : usize = from_str.parse().expect("failed to parse from");
-        //dbg!(from);
-
-        let to_str = l.split_once("to ")
-            .expect("no to in line")
-            .1;
-        let to : usize = to_str.parse().expect("failed to parse to");
-        //dbg!(to);
-
-        let len = stacks[from-1].len();
-        let rem : String = stacks[from-1].drain(len-count..).collect();
-        stacks[to-1].push_str(&rem);
-    }
-    for stack in stacks {
-        print!("{}", stack.chars().rev().next().expect("stack empty"));
-    }
-    println!("");
+    let stacks = stacks.iter().map(|s| Stack::from_bytes(s)).collect();
+
+    // moves start after the drawing and the blank line separating it
+    let first_line = parts[0].lines().count() + 2;
+    let mut moves = Vec::new();
+    for (i, l) in parts[1].lines().enumerate() {
+        match parse_move(l, first_line + i) {
+            Ok(mv) => moves.push(mv),
+            Err(e) => {
+                eprintln!("error: {e}");
+                process::exit(1);
+            }
+        }
+    }
+
+    // with --reverse the drawing is the final arrangement, i.e. the state
+    // after all moves
+    let nmoves = moves.len();
+    let mut sim = Simulation {
+        stacks,
+        moves,
+        done: if opts.reverse { nmoves } else { 0 },
+        crane: opts.crane,
+        lenient: opts.lenient,
+    };
+    if let Some(t) = opts.trace.as_mut() {
+        t.record(if opts.reverse { "final" } else { "initial" }, &sim.stacks);
+    }
+
+    loop {
+        let res = if opts.reverse { sim.undo() } else { sim.redo() };
+        match res {
+            None => break,
+            Some(Ok(None)) => {}
+            Some(Ok(Some(w))) => eprintln!("warning: {w}"),
+            Some(Err(e)) => {
+                eprintln!("error: {e}");
+                process::exit(1);
+            }
+        }
+
+        if let Some(t) = opts.trace.as_mut() {
+            let (steps, verb, mv) = if opts.reverse {
+                (nmoves - sim.done, "undoing", &sim.moves[sim.done])
+            } else {
+                (sim.done, "applying", &sim.moves[sim.done - 1])
+            };
+            if steps % t.every == 0 || steps == nmoves {
+                t.record(
+                    &format!(
+                        "after {verb} line {}: move {} from {} to {}",
+                        mv.line, mv.count, mv.from, mv.to
+                    ),
+                    &sim.stacks,
+                );
+            }
+        }
+    }
+
+    if opts.reverse {
+        print!("{}", render_stacks(&sim.stacks));
+        return;
+    }
+    // lenient moves can empty a stack, which shows as a space
+    for stack in sim.stacks {
+        print!("{}", stack.top().map_or(' ', char::from));
+    }
+    println!();
 }
@@ -1,13 +1,26 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::env;
+use std::fmt;
+use std::fs;
 use std::io;
 use std::io::Read;
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Instant;
 
 const MARKER_SIZE : usize = 14;
 
 fn is_marker(buf: &[u8]) -> bool {
-    for i in 0..buf.len() {
-        let b = buf[i];
-        for j in 0..buf.len() {
-            if i != j && buf[j] == b {
+    for (i, b) in buf.iter().enumerate() {
+        for (j, c) in buf.iter().enumerate() {
+            if i != j && c == b {
                 return false;
             }
         }
@@ -15,17 +28,864 @@ fn is_marker(buf: &[u8]) -> bool {
     true
 }
 
-fn first_marker(b: &Vec<u8>) -> Option<usize> {
-    for i in 0..b.len()-MARKER_SIZE {
-        if is_marker(&b[i..i+MARKER_SIZE]) {
-            return Some(i+MARKER_SIZE)
+// the original quadratic version, kept to benchmark against
+fn first_marker_naive(b: &[u8], size: usize) -> Option<usize> {
+    for i in 0..=b.len().checked_sub(size)? {
+        if is_marker(&b[i..i+size]) {
+            return Some(i+size)
         }
     }
     None
 }
 
+// Keeps the last `size` symbols in a ring buffer together with a count of
+// every symbol in it and of how many distinct symbols there are. The window is
+// a marker exactly when all of its symbols are distinct.
+struct Window {
+    size: usize,
+    ring: Vec<usize>,
+    counts: Vec<usize>,
+    distinct: usize,
+    seen: usize,
+}
+
+impl Window {
+    fn new(size: usize) -> Window {
+        Window {
+            size,
+            ring: vec![0; size],
+            counts: vec![0; 256],
+            distinct: 0,
+            seen: 0,
+        }
+    }
+
+    // returns whether the window ending with symbol `c` is a marker
+    fn push(&mut self, c: usize) -> bool {
+        if c >= self.counts.len() {
+            self.counts.resize(c + 1, 0);
+        }
+        let slot = self.seen % self.size;
+        if self.seen >= self.size {
+            let old = self.ring[slot];
+            self.counts[old] -= 1;
+            if self.counts[old] == 0 {
+                self.distinct -= 1;
+            }
+        }
+        self.ring[slot] = c;
+        self.counts[c] += 1;
+        if self.counts[c] == 1 {
+            self.distinct += 1;
+        }
+        self.seen += 1;
+        self.distinct == self.size
+    }
+}
+
+enum Alphabet {
+    Bytes,
+    Unicode,
+    // only these characters are symbols, others are skipped or rejected
+    Chars(String),
+}
+
+#[derive(Debug)]
+struct DecodeError {
+    descr: &'static str,
+    // offset in bytes from the start of the stream
+    offset: usize,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.descr, self.offset)
+    }
+}
+
+// Turns raw chunks into symbols. Every symbol gets a small integer id: the
+// byte itself, the position in the alphabet, or for Unicode the order in which
+// characters were first seen. Symbols that don't take part in markers (such as
+// ignored whitespace) are passed on without an id so they still count towards
+// positions.
+struct Decoder {
+    alphabet: Alphabet,
+    ignore_whitespace: bool,
+    strict: bool,
+    ids: HashMap<char, usize>,
+    // start of a UTF-8 sequence split between chunks
+    pending: Vec<u8>,
+    offset: usize,
+}
+
+impl Decoder {
+    fn new(alphabet: Alphabet, ignore_whitespace: bool, strict: bool) -> Decoder {
+        let mut ids = HashMap::new();
+        if let Alphabet::Chars(chars) = &alphabet {
+            for c in chars.chars() {
+                let next = ids.len();
+                ids.entry(c).or_insert(next);
+            }
+        }
+        Decoder {
+            alphabet,
+            ignore_whitespace,
+            strict,
+            ids,
+            pending: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    fn decode<F: FnMut(Option<usize>, &[u8])>(&mut self, chunk: &[u8], mut emit: F) -> Result<(), DecodeError> {
+        if let Alphabet::Bytes = self.alphabet {
+            for c in chunk {
+                let ignored = self.ignore_whitespace && c.is_ascii_whitespace();
+                emit(if ignored { None } else { Some(*c as usize) }, std::slice::from_ref(c));
+            }
+            self.offset += chunk.len();
+            return Ok(());
+        }
+
+        let mut data = std::mem::take(&mut self.pending);
+        data.extend_from_slice(chunk);
+        let valid = match std::str::from_utf8(&data) {
+            Ok(s) => s,
+            Err(e) => {
+                if e.error_len().is_some() {
+                    return Err(DecodeError {
+                        descr: "invalid UTF-8",
+                        offset: self.offset + e.valid_up_to(),
+                    });
+                }
+                std::str::from_utf8(&data[..e.valid_up_to()]).unwrap()
+            }
+        };
+        for (i, c) in valid.char_indices() {
+            let raw = &data[i..i + c.len_utf8()];
+            if self.ignore_whitespace && c.is_whitespace() {
+                emit(None, raw);
+                continue;
+            }
+            let id = match self.alphabet {
+                Alphabet::Chars(_) => self.ids.get(&c).copied(),
+                _ => {
+                    let next = self.ids.len();
+                    Some(*self.ids.entry(c).or_insert(next))
+                }
+            };
+            if id.is_none() && self.strict {
+                return Err(DecodeError {
+                    descr: "character outside the alphabet",
+                    offset: self.offset + i,
+                });
+            }
+            emit(id, raw);
+        }
+        let used = valid.len();
+        self.offset += used;
+        self.pending = data.split_off(used);
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<(), DecodeError> {
+        if !self.pending.is_empty() {
+            return Err(DecodeError {
+                descr: "truncated UTF-8 sequence",
+                offset: self.offset,
+            });
+        }
+        Ok(())
+    }
+}
+
+struct MarkerEvent {
+    size: usize,
+    // number of symbols in the stream up to and including the marker
+    pos: usize,
+}
+
+// Detects markers of several sizes in a stream that arrives in arbitrary
+// chunks. Memory use only depends on the marker sizes and the alphabet, not on
+// the length of the stream.
+struct MarkerDetector {
+    decoder: Decoder,
+    windows: Vec<Window>,
+    pos: usize,
+}
+
+impl MarkerDetector {
+    fn new(decoder: Decoder, sizes: &[usize]) -> MarkerDetector {
+        MarkerDetector {
+            decoder,
+            windows: sizes.iter().map(|&size| Window::new(size)).collect(),
+            pos: 0,
+        }
+    }
+
+    fn feed<F: FnMut(MarkerEvent)>(&mut self, chunk: &[u8], mut emit: F) -> Result<(), DecodeError> {
+        let windows = &mut self.windows;
+        let pos = &mut self.pos;
+        self.decoder.decode(chunk, |id, _| {
+            *pos += 1;
+            let Some(id) = id else { return };
+            for w in windows.iter_mut() {
+                if w.push(id) {
+                    emit(MarkerEvent {
+                        size: w.size,
+                        pos: *pos,
+                    });
+                }
+            }
+        })
+    }
+}
+
+struct Frame {
+    index: usize,
+    // stream position of the first payload symbol
+    offset: usize,
+    byte_offset: usize,
+    payload: Vec<u8>,
+    // frames of the next smaller marker size inside this one
+    children: Vec<Frame>,
+}
+
+impl Frame {
+    // Ends the frame, and everything nested in it, where a marker starting
+    // at byte `end` begins. Nested frames that only started inside that
+    // marker are dropped.
+    fn cut(&mut self, end: usize) {
+        self.payload.truncate(end.saturating_sub(self.byte_offset));
+        self.children.retain(|c| c.byte_offset <= end);
+        for c in &mut self.children {
+            c.cut(end);
+        }
+    }
+}
+
+struct Level {
+    window: Window,
+    current: Option<Frame>,
+}
+
+// Splits a stream into frames, each starting after a marker and running up to
+// the start of the next one. The window is reset after every marker so that
+// successive markers never overlap. Symbols before the first marker are
+// dropped.
+//
+// With several marker sizes the frames nest: the largest markers delimit the
+// outermost frames (messages), and each smaller size splits the frames of the
+// next larger one (packets). A marker ends the frames of all smaller sizes
+// too, and those only look for markers inside a frame of the next larger
+// size.
+struct Framer {
+    decoder: Decoder,
+    // largest marker size first
+    levels: Vec<Level>,
+    pos: usize,
+    byte_pos: usize,
+    // byte offsets of the last symbols that went into the windows
+    starts: VecDeque<usize>,
+    next_index: usize,
+}
+
+impl Framer {
+    fn new(decoder: Decoder, sizes: &[usize]) -> Framer {
+        let mut sizes = sizes.to_vec();
+        sizes.sort_by(|a, b| b.cmp(a));
+        Framer {
+            decoder,
+            levels: sizes
+                .iter()
+                .map(|&size| Level {
+                    window: Window::new(size),
+                    current: None,
+                })
+                .collect(),
+            pos: 0,
+            byte_pos: 0,
+            starts: VecDeque::new(),
+            next_index: 0,
+        }
+    }
+
+    // Closes the open frames from level `from` inwards, each one going into
+    // its parent's children, or to `emit` for the outermost level.
+    fn close<F: FnMut(Frame)>(levels: &mut [Level], from: usize, emit: &mut F) {
+        for k in (from..levels.len()).rev() {
+            let Some(f) = levels[k].current.take() else {
+                continue;
+            };
+            match k {
+                0 => emit(f),
+                // frames only start inside a frame of the level above
+                _ => levels[k - 1].current.as_mut().unwrap().children.push(f),
+            }
+        }
+    }
+
+    fn feed<F: FnMut(Frame)>(&mut self, chunk: &[u8], mut emit: F) -> Result<(), DecodeError> {
+        let Framer {
+            decoder,
+            levels,
+            pos,
+            byte_pos,
+            starts,
+            next_index,
+        } = self;
+        let max_size = levels.first().map_or(0, |l| l.window.size);
+        decoder.decode(chunk, |id, raw| {
+            let start = *byte_pos;
+            *pos += 1;
+            *byte_pos += raw.len();
+            for l in levels.iter_mut() {
+                if let Some(f) = &mut l.current {
+                    f.payload.extend_from_slice(raw);
+                }
+            }
+            let Some(id) = id else { return };
+            starts.push_back(start);
+            if starts.len() > max_size {
+                starts.pop_front();
+            }
+            for j in 0..levels.len() {
+                if j > 0 && levels[j - 1].current.is_none() {
+                    return;
+                }
+                if !levels[j].window.push(id) {
+                    continue;
+                }
+                let end = starts[starts.len() - levels[j].window.size];
+                for l in &mut levels[j..] {
+                    if l.current.as_ref().is_some_and(|f| f.byte_offset > end) {
+                        // started by a smaller marker inside this one
+                        l.current = None;
+                    }
+                    if let Some(f) = &mut l.current {
+                        f.cut(end);
+                    }
+                }
+                Framer::close(levels, j, &mut emit);
+                let index = match j {
+                    0 => {
+                        *next_index += 1;
+                        *next_index - 1
+                    }
+                    _ => levels[j - 1].current.as_ref().unwrap().children.len(),
+                };
+                levels[j].current = Some(Frame {
+                    index,
+                    offset: *pos,
+                    byte_offset: *byte_pos,
+                    payload: Vec::new(),
+                    children: Vec::new(),
+                });
+                for l in &mut levels[j..] {
+                    l.window = Window::new(l.window.size);
+                }
+                return;
+            }
+        })
+    }
+
+    fn finish<F: FnMut(Frame)>(&mut self, mut emit: F) -> Result<(), DecodeError> {
+        Framer::close(&mut self.levels, 0, &mut emit);
+        self.decoder.finish()
+    }
+}
+
+fn hexdump(offset: usize, b: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, line) in b.chunks(16).enumerate() {
+        let hex: Vec<String> = line.iter().map(|c| format!("{:02x}", c)).collect();
+        let ascii: String = line
+            .iter()
+            .map(|&c| if c.is_ascii_graphic() || c == b' ' { char::from(c) } else { '.' })
+            .collect();
+        out.push_str(&format!("{:08x}  {:<47}  |{}|\n", offset + i * 16, hex.join(" "), ascii));
+    }
+    out
+}
+
+fn first_marker(b: &[u8], size: usize) -> Option<usize> {
+    let mut w = Window::new(size);
+    b.iter().position(|&c| w.push(c as usize)).map(|i| i + 1)
+}
+
+// Input where every window of 14 bytes has a repeat except the last one, which
+// is the worst case for both implementations. The cycle of 13 letters always
+// repeats one within 14 bytes, then a run of `a`s keeps repeating until the
+// window holds only the last `a` and 13 new letters.
+fn bench(len: usize) {
+    let mut b: Vec<u8> = (0..len).map(|i| b'a' + (i % (MARKER_SIZE - 1)) as u8).collect();
+    b.extend([b'a'; MARKER_SIZE]);
+    b.extend(b'n'..b'n' + (MARKER_SIZE - 1) as u8);
+
+    let start = Instant::now();
+    let naive = first_marker_naive(&b, MARKER_SIZE);
+    let t_naive = start.elapsed();
+    let start = Instant::now();
+    let window = first_marker(&b, MARKER_SIZE);
+    let t_window = start.elapsed();
+
+    if naive != window {
+        panic!("implementations disagree: {naive:?} != {window:?}");
+    }
+    println!("{} bytes, marker at {:?}", b.len(), window);
+    println!("naive:  {:?}", t_naive);
+    println!("window: {:?}", t_window);
+    println!("speedup: {:.1}x", t_naive.as_secs_f64() / t_window.as_secs_f64());
+}
+
+struct Options {
+    sizes: Vec<usize>,
+    first_only: bool,
+    json: bool,
+    bench: Option<usize>,
+    socket: Option<String>,
+    frames: bool,
+    hexdump: bool,
+    unicode: bool,
+    alphabet: Option<String>,
+    ignore_whitespace: bool,
+    strict: bool,
+    batch: bool,
+    jobs: usize,
+    paths: Vec<String>,
+}
+
+impl Options {
+    fn from_args() -> Options {
+        let mut opts = Options {
+            sizes: Vec::new(),
+            first_only: false,
+            json: false,
+            bench: None,
+            socket: None,
+            frames: false,
+            hexdump: false,
+            unicode: false,
+            alphabet: None,
+            ignore_whitespace: false,
+            strict: false,
+            batch: false,
+            jobs: thread::available_parallelism().map_or(1, |n| n.get()),
+            paths: Vec::new(),
+        };
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--size" => {
+                    let v = args.next().expect("--size needs a value");
+                    for s in v.split(',') {
+                        let size = s.parse().expect("failed to parse marker size");
+                        if size == 0 {
+                            panic!("marker size must be at least 1");
+                        }
+                        opts.sizes.push(size);
+                    }
+                }
+                "--first" => opts.first_only = true,
+                "--json" => opts.json = true,
+                "--bench" => {
+                    opts.bench = Some(10_000_000);
+                }
+                "--bench-len" => {
+                    let v = args.next().expect("--bench-len needs a value");
+                    opts.bench = Some(v.parse().expect("failed to parse benchmark length"));
+                }
+                "--frames" => opts.frames = true,
+                "--hexdump" => opts.hexdump = true,
+                "--unicode" => opts.unicode = true,
+                "--alphabet" => {
+                    opts.alphabet = Some(args.next().expect("--alphabet needs a list of characters"));
+                }
+                "--ignore-whitespace" => opts.ignore_whitespace = true,
+                "--strict" => opts.strict = true,
+                "--socket" => {
+                    opts.socket = Some(args.next().expect("--socket needs a path"));
+                }
+                "--batch" => opts.batch = true,
+                "--jobs" => {
+                    let v = args.next().expect("--jobs needs a value");
+                    opts.jobs = v.parse().expect("failed to parse --jobs");
+                    if opts.jobs == 0 {
+                        panic!("--jobs must be at least 1");
+                    }
+                }
+                _ if !arg.starts_with("--") => opts.paths.push(arg),
+                _ => panic!("unknown argument {arg}"),
+            }
+        }
+        if !opts.paths.is_empty() && !opts.batch {
+            panic!("file arguments are only supported with --batch");
+        }
+        if opts.batch && opts.socket.is_some() {
+            panic!("--batch reads files, it can't be combined with --socket");
+        }
+        // bytes and Unicode characters are all symbols, only an alphabet has
+        // something to be strict about
+        if opts.strict && opts.alphabet.is_none() {
+            eprintln!("error: --strict needs --alphabet");
+            process::exit(1);
+        }
+        opts
+    }
+
+    fn marker_sizes(&self) -> Vec<usize> {
+        let mut sizes = if self.sizes.is_empty() {
+            vec![MARKER_SIZE]
+        } else {
+            self.sizes.clone()
+        };
+        sizes.sort();
+        sizes.dedup();
+        sizes
+    }
+
+    fn decoder(&self) -> Decoder {
+        let alphabet = match &self.alphabet {
+            Some(chars) => Alphabet::Chars(chars.clone()),
+            None if self.unicode => Alphabet::Unicode,
+            None => Alphabet::Bytes,
+        };
+        Decoder::new(alphabet, self.ignore_whitespace, self.strict)
+    }
+}
+
+fn fail(e: DecodeError) -> ! {
+    eprintln!("error: {e}");
+    process::exit(1);
+}
+
+// Calls `f` with every chunk read from `input` until it returns false or the
+// input ends.
+fn read_chunks<F: FnMut(&[u8]) -> bool>(input: &mut dyn Read, mut f: F) -> io::Result<()> {
+    let mut buf = [0; 8192];
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if !f(&buf[..n]) {
+            return Ok(());
+        }
+    }
+}
+
+fn run_markers(opts: &Options, input: &mut dyn Read) {
+    // without any options only the first start-of-message marker is printed
+    let plain = opts.sizes.is_empty() && !opts.json;
+    let first_only = opts.first_only || plain;
+    let sizes = opts.marker_sizes();
+    let mut detector = MarkerDetector::new(opts.decoder(), &sizes);
+    let mut found = vec![false; sizes.len()];
+
+    if !plain && !opts.json {
+        println!("{:>4}  {:>8}", "size", "position");
+    }
+    read_chunks(input, |chunk| {
+        let mut out = io::stdout().lock();
+        let res = detector.feed(chunk, |ev| {
+            let i = sizes.iter().position(|&s| s == ev.size).unwrap();
+            if first_only && found[i] {
+                return;
+            }
+            found[i] = true;
+            if plain {
+                writeln!(out, "{}", ev.pos).unwrap();
+            } else if opts.json {
+                // one object per line as soon as the marker is seen, so
+                // endless streams produce output too
+                writeln!(out, "{{\"size\":{},\"pos\":{}}}", ev.size, ev.pos).unwrap();
+            } else {
+                writeln!(out, "{:>4}  {:>8}", ev.size, ev.pos).unwrap();
+            }
+        });
+        out.flush().unwrap();
+        if let Err(e) = res {
+            fail(e);
+        }
+        !(first_only && found.iter().all(|&f| f))
+    })
+    .expect("failed to read input");
+
+    if let Err(e) = detector.decoder.finish() {
+        fail(e);
+    }
+    if plain {
+        if !found[0] {
+            eprintln!("input should contain marker");
+            process::exit(1);
+        }
+    } else if !opts.json {
+        for (i, size) in sizes.iter().enumerate() {
+            if !found[i] {
+                println!("{:>4}  {:>8}", size, "-");
+            }
+        }
+    }
+}
+
+// Prints a frame and the frames nested in it, indented by depth and
+// numbered like 3.1 for the second packet of the fourth message.
+fn print_frame(opts: &Options, f: &Frame, label: &str, depth: usize) {
+    let indent = "  ".repeat(depth);
+    println!(
+        "{indent}frame {label}: offset {}, length {} bytes",
+        f.offset,
+        f.payload.len()
+    );
+    if opts.hexdump {
+        for line in hexdump(f.byte_offset, &f.payload).lines() {
+            println!("{indent}{line}");
+        }
+    } else {
+        println!("{indent}{:?}", String::from_utf8_lossy(&f.payload));
+    }
+    for c in &f.children {
+        print_frame(opts, c, &format!("{label}.{}", c.index), depth + 1);
+    }
+}
+
+fn run_frames(opts: &Options, input: &mut dyn Read) {
+    let sizes = if opts.sizes.is_empty() {
+        vec![4]
+    } else {
+        opts.marker_sizes()
+    };
+    let mut framer = Framer::new(opts.decoder(), &sizes);
+    let print = |f: Frame| print_frame(opts, &f, &f.index.to_string(), 0);
+    read_chunks(input, |chunk| {
+        if let Err(e) = framer.feed(chunk, print) {
+            fail(e);
+        }
+        true
+    })
+    .expect("failed to read input");
+    if let Err(e) = framer.finish(print) {
+        fail(e);
+    }
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return;
+    }
+    let mut entries: Vec<PathBuf> = match fs::read_dir(path) {
+        Ok(rd) => rd.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(_) => {
+            // report it like any other unreadable file
+            files.push(path.to_path_buf());
+            return;
+        }
+    };
+    entries.sort();
+    for e in entries {
+        collect_files(&e, files);
+    }
+}
+
+// Returns the first marker position for every size, stopping as soon as all of
+// them have been found.
+fn scan_file(opts: &Options, sizes: &[usize], path: &Path) -> Result<Vec<Option<usize>>, String> {
+    let mut f = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut detector = MarkerDetector::new(opts.decoder(), sizes);
+    let mut first = vec![None; sizes.len()];
+    let mut err = None;
+    read_chunks(&mut f, |chunk| {
+        let res = detector.feed(chunk, |ev| {
+            let i = sizes.iter().position(|&s| s == ev.size).unwrap();
+            first[i].get_or_insert(ev.pos);
+        });
+        if let Err(e) = res {
+            err = Some(e);
+            return false;
+        }
+        first.iter().any(|p| p.is_none())
+    })
+    .map_err(|e| e.to_string())?;
+    if let Some(e) = err {
+        return Err(e.to_string());
+    }
+    if first.iter().any(|p| p.is_none()) {
+        detector.decoder.finish().map_err(|e| e.to_string())?;
+    }
+    Ok(first)
+}
+
+fn print_distribution(positions: &mut [usize]) {
+    positions.sort();
+    let n = positions.len();
+    let mean = positions.iter().sum::<usize>() as f64 / n as f64;
+    println!(
+        "  min {}, median {}, mean {:.1}, max {}",
+        positions[0],
+        positions[n / 2],
+        mean,
+        positions[n - 1]
+    );
+    // power of two buckets: 1, 2-3, 4-7, ...
+    let mut buckets: Vec<usize> = Vec::new();
+    for &p in positions.iter() {
+        let b = (usize::BITS - p.leading_zeros()) as usize;
+        if b >= buckets.len() {
+            buckets.resize(b + 1, 0);
+        }
+        buckets[b] += 1;
+    }
+    for (b, &count) in buckets.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        let lo = 1usize << (b - 1);
+        let hi = (lo << 1) - 1;
+        println!("  {:>10}  {:>6}", format!("{lo}-{hi}"), count);
+    }
+}
+
+fn run_batch(opts: &Options) {
+    let mut paths = opts.paths.clone();
+    if paths.is_empty() {
+        for l in io::stdin().lines() {
+            let l = l.expect("failed to read file list");
+            if !l.trim().is_empty() {
+                paths.push(String::from(l.trim()));
+            }
+        }
+    }
+    let mut files = Vec::new();
+    for p in &paths {
+        collect_files(Path::new(p), &mut files);
+    }
+    let sizes = opts.marker_sizes();
+
+    // workers take the next file index until all files are scanned
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<Option<Result<Vec<Option<usize>>, String>>> = vec![None; files.len()];
+    thread::scope(|s| {
+        let handles: Vec<_> = (0..opts.jobs.min(files.len()))
+            .map(|_| {
+                s.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= files.len() {
+                            return done;
+                        }
+                        done.push((i, scan_file(opts, &sizes, &files[i])));
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            for (i, r) in h.join().expect("worker panicked") {
+                results[i] = Some(r);
+            }
+        }
+    });
+
+    let header: Vec<String> = sizes.iter().map(|s| format!("{:>9}", format!("size {s}"))).collect();
+    println!("{}  file", header.join(""));
+    let mut positions = vec![Vec::new(); sizes.len()];
+    let mut missing = vec![Vec::new(); sizes.len()];
+    let mut errors = 0;
+    for (path, r) in files.iter().zip(results) {
+        match r.expect("file was not scanned") {
+            Ok(first) => {
+                let cols: Vec<String> = first
+                    .iter()
+                    .map(|p| format!("{:>9}", p.map_or(String::from("-"), |p| p.to_string())))
+                    .collect();
+                println!("{}  {}", cols.join(""), path.display());
+                for (i, p) in first.iter().enumerate() {
+                    match p {
+                        Some(p) => positions[i].push(*p),
+                        None => missing[i].push(path),
+                    }
+                }
+            }
+            Err(e) => {
+                errors += 1;
+                println!("{:>9}  {}: {}", "error", path.display(), e);
+            }
+        }
+    }
+
+    println!();
+    println!("{} files, {} errors", files.len(), errors);
+    for (i, size) in sizes.iter().enumerate() {
+        println!("size {}: {} with marker, {} without", size, positions[i].len(), missing[i].len());
+        if !positions[i].is_empty() {
+            print_distribution(&mut positions[i]);
+        }
+        for p in &missing[i] {
+            println!("  no marker: {}", p.display());
+        }
+    }
+}
+
 fn main() {
-    let mut b = Vec::new();
-    io::stdin().read_to_end(&mut b).expect("failed to read input");
-    println!("{}", first_marker(&b).expect("input should contain marker"));
+    let opts = Options::from_args();
+    if let Some(len) = opts.bench {
+        bench(len);
+        return;
+    }
+
+    if opts.batch {
+        run_batch(&opts);
+        return;
+    }
+
+    let mut input: Box<dyn Read> = match &opts.socket {
+        Some(path) => Box::new(UnixStream::connect(path).expect("failed to connect to socket")),
+        None => Box::new(io::stdin()),
+    };
+
+    if opts.frames {
+        run_frames(&opts, &mut input);
+    } else {
+        run_markers(&opts, &mut input);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(input: &[u8], sizes: &[usize], chunk: usize) -> Vec<Frame> {
+        let mut framer = Framer::new(Decoder::new(Alphabet::Bytes, false, false), sizes);
+        let mut out = Vec::new();
+        for c in input.chunks(chunk) {
+            framer.feed(c, |f| out.push(f)).unwrap();
+        }
+        framer.finish(|f| out.push(f)).unwrap();
+        out
+    }
+
+    #[test]
+    fn nested_frames() {
+        let input = b"zzabcdefAAAxyzBBBBBuvwCCCC##uvwxyzDDDD";
+        for chunk in [1, 5, input.len()] {
+            let f = frames(input, &[3, 6], chunk);
+            assert_eq!(f.len(), 2);
+            assert_eq!(f[0].payload, b"fAAAxyzBBBBBuvwCCCC#");
+            let packets: Vec<&[u8]> = f[0].children.iter().map(|c| &c.payload[..]).collect();
+            // the packet marker inside the message marker doesn't start one
+            assert_eq!(packets, [&b"zBBBB"[..], &b"wCCCC#"[..]]);
+            assert_eq!(f[1].payload, b"zDDDD");
+            assert!(f[1].children.is_empty());
+        }
+    }
+
+    #[test]
+    fn single_size_frames() {
+        let f = frames(b"aabcxxxxdefyy", &[3], 4);
+        let payloads: Vec<&[u8]> = f.iter().map(|f| &f.payload[..]).collect();
+        assert_eq!(payloads, [&b"xxx"[..], &b"fyy"[..]]);
+    }
 }
@@ -0,0 +1,385 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::fs::FileSystem;
+use crate::fs::NodeId;
+
+pub struct Capacity {
+    pub disk: usize,
+    pub required: usize,
+}
+
+#[derive(Debug)]
+pub enum CapacityError {
+    Overfull { used: usize, disk: usize },
+    RequiredTooLarge { required: usize, disk: usize },
+}
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CapacityError::Overfull { used, disk } => {
+                write!(f, "{used} bytes used, more than the disk size of {disk}")
+            }
+            CapacityError::RequiredTooLarge { required, disk } => {
+                write!(f, "{required} bytes required, more than the disk size of {disk}")
+            }
+        }
+    }
+}
+
+impl Capacity {
+    // How much has to be deleted to get the required free space, zero if
+    // there is already enough. Deleting exactly this many bytes is enough:
+    // everything that picks directories to delete accepts sizes >= this.
+    pub fn to_be_freed(&self, fs: &FileSystem) -> Result<usize, CapacityError> {
+        let used = fs.size(fs.root());
+        if used > self.disk {
+            return Err(CapacityError::Overfull {
+                used,
+                disk: self.disk,
+            });
+        }
+        if self.required > self.disk {
+            return Err(CapacityError::RequiredTooLarge {
+                required: self.required,
+                disk: self.disk,
+            });
+        }
+        Ok(self.required.saturating_sub(self.disk - used))
+    }
+}
+
+// directories below the root smaller than `lim`, smallest first
+pub fn small_dirs(fs: &FileSystem, lim: usize) -> Vec<NodeId> {
+    let mut dirs: Vec<NodeId> = fs
+        .dirs()
+        .filter(|&d| d != fs.root() && fs.size(d) < lim)
+        .collect();
+    dirs.sort_by_key(|&d| fs.size(d));
+    dirs
+}
+
+// directories below the root that free at least `need` on their own,
+// smallest first
+pub fn candidates(fs: &FileSystem, need: usize) -> Vec<NodeId> {
+    let mut dirs: Vec<NodeId> = fs
+        .dirs()
+        .filter(|&d| d != fs.root() && fs.size(d) >= need)
+        .collect();
+    dirs.sort_by_key(|&d| fs.size(d));
+    dirs
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Objective {
+    // delete as few bytes as possible
+    Bytes,
+    // delete as few files and directories as possible, then as few bytes
+    Items,
+}
+
+pub struct Plan {
+    pub nodes: Vec<NodeId>,
+    pub freed: usize,
+    pub exact: bool,
+}
+
+impl Objective {
+    fn key(self, freed: usize, items: usize) -> (usize, usize) {
+        match self {
+            Objective::Bytes => (freed, items),
+            Objective::Items => (items, freed),
+        }
+    }
+}
+
+// The nodes of a partial solution, shared between solutions instead of
+// copied: a list is a single node or two lists joined, stored in an arena and
+// referred to by index.
+enum Part {
+    Node(NodeId),
+    Join(usize, usize),
+}
+
+// A partial solution: bytes freed, number of items and the nodes, None when
+// empty.
+#[derive(Clone, Copy)]
+struct State {
+    freed: usize,
+    items: usize,
+    nodes: Option<usize>,
+}
+
+// Partial solutions of a subtree that don't free enough yet, keyed by the bytes
+// they free. For the same number of bytes only the one with the fewest items
+// is worth keeping, whatever the objective.
+type Frontier = HashMap<usize, State>;
+
+struct Exact<'a> {
+    fs: &'a FileSystem,
+    need: usize,
+    objective: Objective,
+    best: Option<State>,
+    parts: Vec<Part>,
+    max_states: usize,
+    // pairs of partial solutions that may still be combined before giving up
+    work: usize,
+}
+
+impl Exact<'_> {
+    fn join(&mut self, a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        match (a, b) {
+            (Some(a), Some(b)) => {
+                self.parts.push(Part::Join(a, b));
+                Some(self.parts.len() - 1)
+            }
+            _ => a.or(b),
+        }
+    }
+
+    fn offer(&mut self, s1: State, s2: State, frontier: &mut Frontier) {
+        let freed = s1.freed + s2.freed;
+        let items = s1.items + s2.items;
+        if freed >= self.need {
+            let better = match &self.best {
+                None => true,
+                Some(b) => self.objective.key(freed, items) < self.objective.key(b.freed, b.items),
+            };
+            if better {
+                let nodes = self.join(s1.nodes, s2.nodes);
+                self.best = Some(State { freed, items, nodes });
+            }
+            return;
+        }
+        match frontier.get(&freed) {
+            Some(s) if s.items <= items => {}
+            _ => {
+                let nodes = self.join(s1.nodes, s2.nodes);
+                frontier.insert(freed, State { freed, items, nodes });
+            }
+        }
+    }
+
+    // returns None when the number of partial solutions gets too large
+    fn frontier(&mut self, id: NodeId) -> Option<Frontier> {
+        const EMPTY: State = State {
+            freed: 0,
+            items: 0,
+            nodes: None,
+        };
+        let mut acc = Frontier::new();
+        acc.insert(0, EMPTY);
+        for &c in self.fs.children(id) {
+            let child = self.frontier(c)?;
+            self.work = self.work.checked_sub(acc.len() * child.len())?;
+            let mut next = Frontier::new();
+            for s1 in acc.values() {
+                for s2 in child.values() {
+                    self.offer(*s1, *s2, &mut next);
+                    if next.len() > self.max_states {
+                        return None;
+                    }
+                }
+            }
+            acc = next;
+        }
+        // deleting the node itself instead of parts of it
+        if id != self.fs.root() {
+            self.parts.push(Part::Node(id));
+            let node = State {
+                freed: self.fs.size(id),
+                items: 1,
+                nodes: Some(self.parts.len() - 1),
+            };
+            self.offer(node, EMPTY, &mut acc);
+        }
+        Some(acc)
+    }
+
+    fn nodes(&self, part: Option<usize>) -> Vec<NodeId> {
+        let mut nodes = Vec::new();
+        let mut todo: Vec<usize> = part.into_iter().collect();
+        while let Some(p) = todo.pop() {
+            match self.parts[p] {
+                Part::Node(id) => nodes.push(id),
+                Part::Join(a, b) => todo.extend([b, a]),
+            }
+        }
+        nodes
+    }
+}
+
+// how many pairs of partial solutions are combined per allowed state before
+// the exact search gives up
+const WORK_PER_STATE: usize = 100;
+
+// Finds the best set of files and directories to delete, none of them inside
+// another, with a dynamic program over the tree. Gives up and returns None
+// when the tree has too many different partial sums.
+pub fn plan_exact(fs: &FileSystem, need: usize, objective: Objective, max_states: usize) -> Option<Option<Plan>> {
+    let mut e = Exact {
+        fs,
+        need,
+        objective,
+        best: None,
+        parts: Vec::new(),
+        max_states,
+        work: max_states.saturating_mul(WORK_PER_STATE),
+    };
+    e.frontier(fs.root())?;
+    Some(e.best.map(|b| Plan {
+        nodes: e.nodes(b.nodes),
+        freed: b.freed,
+        exact: true,
+    }))
+}
+
+struct Selection<'a> {
+    fs: &'a FileSystem,
+    chosen: Vec<bool>,
+    chosen_below: Vec<bool>,
+    nodes: Vec<NodeId>,
+    freed: usize,
+}
+
+impl Selection<'_> {
+    fn conflicts(&self, id: NodeId) -> bool {
+        if self.chosen[id] || self.chosen_below[id] {
+            return true;
+        }
+        let mut p = self.fs.parent(id);
+        while let Some(d) = p {
+            if self.chosen[d] {
+                return true;
+            }
+            p = self.fs.parent(d);
+        }
+        false
+    }
+
+    fn choose(&mut self, id: NodeId) {
+        self.chosen[id] = true;
+        self.nodes.push(id);
+        self.freed += self.fs.size(id);
+        let mut p = self.fs.parent(id);
+        while let Some(d) = p {
+            self.chosen_below[d] = true;
+            p = self.fs.parent(d);
+        }
+    }
+}
+
+// Greedy plan for trees too large for the exact search: take the largest
+// entries that still fit into what has to be freed, then close the remaining
+// gap with the smallest entry that covers it. The best single directory or
+// file is used instead if it is better.
+pub fn plan_heuristic(fs: &FileSystem, need: usize, objective: Objective) -> Option<Plan> {
+    let mut by_size: Vec<NodeId> = fs.ids().filter(|&id| id != fs.root()).collect();
+    by_size.sort_by_key(|&id| Reverse(fs.size(id)));
+
+    let single = by_size
+        .iter()
+        .rev()
+        .copied()
+        .find(|&id| fs.size(id) >= need)
+        .map(|id| Plan {
+            nodes: vec![id],
+            freed: fs.size(id),
+            exact: false,
+        });
+    if objective == Objective::Items && single.is_some() {
+        return single;
+    }
+
+    let mut sel = Selection {
+        fs,
+        chosen: vec![false; fs.node_count()],
+        chosen_below: vec![false; fs.node_count()],
+        nodes: Vec::new(),
+        freed: 0,
+    };
+    for &id in &by_size {
+        if sel.freed >= need {
+            break;
+        }
+        let fits = sel.freed + fs.size(id) <= need;
+        if (fits || objective == Objective::Items) && fs.size(id) > 0 && !sel.conflicts(id) {
+            sel.choose(id);
+        }
+    }
+    if sel.freed < need {
+        let gap = need - sel.freed;
+        if let Some(&id) = by_size
+            .iter()
+            .rev()
+            .find(|&&id| fs.size(id) >= gap && !sel.conflicts(id))
+        {
+            sel.choose(id);
+        }
+    }
+    let greedy = (sel.freed >= need).then_some(Plan {
+        nodes: sel.nodes,
+        freed: sel.freed,
+        exact: false,
+    });
+
+    [single, greedy]
+        .into_iter()
+        .flatten()
+        .min_by_key(|p| objective.key(p.freed, p.nodes.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript;
+
+    fn load(input: &str) -> FileSystem {
+        transcript::parse(input).unwrap().0
+    }
+
+    #[test]
+    fn directory_of_exactly_the_needed_size() {
+        let fs = load("$ cd /\n$ ls\ndir a\n100 f\n$ cd a\n$ ls\n50 g\n");
+        let need = Capacity { disk: 200, required: 100 }.to_be_freed(&fs).unwrap();
+        assert_eq!(need, 50);
+        let a = fs.child(fs.root(), "a").unwrap();
+        assert_eq!(candidates(&fs, need), [a]);
+        let plan = plan_heuristic(&fs, need, Objective::Items).unwrap();
+        assert_eq!((plan.nodes.len(), plan.freed), (1, 50));
+    }
+
+    #[test]
+    fn exact_plan_gives_up_on_many_partial_sums() {
+        // two directories of 16 files with unrelated sizes have far too many
+        // subset sums to combine
+        let mut input = String::from("$ cd /\n$ ls\ndir a\ndir b\n");
+        let mut size: usize = 1;
+        for dir in ["a", "b"] {
+            input.push_str(&format!("$ cd /{dir}\n$ ls\n"));
+            for i in 0..16 {
+                size = (size * 1103515245 + 12345) % (1 << 31);
+                input.push_str(&format!("{} {dir}{i}\n", 1000 + size % 16000));
+            }
+        }
+        let fs = load(&input);
+        let need = Capacity { disk: 600000, required: 599000 }.to_be_freed(&fs).unwrap();
+        assert!(plan_exact(&fs, need, Objective::Bytes, 100000).is_none());
+        assert!(plan_heuristic(&fs, need, Objective::Bytes).is_some_and(|p| p.freed >= need));
+    }
+
+    #[test]
+    fn exact_plan_on_small_tree() {
+        let fs = load("$ cd /\n$ ls\ndir a\n100 f\n$ cd a\n$ ls\n30 g\n40 h\n");
+        let plan = plan_exact(&fs, 70, Objective::Bytes, 100).unwrap().unwrap();
+        let mut names: Vec<&str> = plan.nodes.iter().map(|&id| fs.node(id).name.as_str()).collect();
+        names.sort();
+        assert_eq!((plan.freed, names), (70, vec!["a"]));
+        let plan = plan_exact(&fs, 120, Objective::Bytes, 100).unwrap().unwrap();
+        let mut names: Vec<&str> = plan.nodes.iter().map(|&id| fs.node(id).name.as_str()).collect();
+        names.sort();
+        assert_eq!((plan.freed, names), (130, vec!["f", "g"]));
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::BTreeSet;
+
+use crate::fs::FileSystem;
+use crate::fs::NodeId;
+
+pub enum Change {
+    Added(NodeId),
+    Removed(NodeId),
+    // a file with a different size, old and new node
+    Resized(NodeId, NodeId),
+    // a directory whose cumulative size changed, old and new node
+    DirSize(NodeId, NodeId),
+}
+
+fn walk(old: &FileSystem, o: NodeId, new: &FileSystem, n: NodeId, changes: &mut Vec<Change>) {
+    let names: BTreeSet<&str> = old
+        .children(o)
+        .iter()
+        .map(|&c| old.node(c).name.as_str())
+        .chain(new.children(n).iter().map(|&c| new.node(c).name.as_str()))
+        .collect();
+    for name in names {
+        match (old.child(o, name), new.child(n, name)) {
+            (Some(a), None) => changes.push(Change::Removed(a)),
+            (None, Some(b)) => changes.push(Change::Added(b)),
+            (Some(a), Some(b)) => match (old.is_dir(a), new.is_dir(b)) {
+                (true, true) => walk(old, a, new, b, changes),
+                (false, false) => {
+                    if old.size(a) != new.size(b) {
+                        changes.push(Change::Resized(a, b));
+                    }
+                }
+                // replaced by an entry of the other kind
+                _ => {
+                    changes.push(Change::Removed(a));
+                    changes.push(Change::Added(b));
+                }
+            },
+            (None, None) => unreachable!(),
+        }
+    }
+    if old.size(o) != new.size(n) {
+        changes.push(Change::DirSize(o, n));
+    }
+}
+
+// Compares two filesystems reconstructed from transcripts of the same device.
+// Entries are matched by path. Directory size changes come after the entries
+// in them.
+pub fn diff(old: &FileSystem, new: &FileSystem) -> Vec<Change> {
+    let mut changes = Vec::new();
+    walk(old, old.root(), new, new.root(), &mut changes);
+    changes
+}
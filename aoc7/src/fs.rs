@@ -0,0 +1,162 @@
+pub type NodeId = usize;
+
+pub enum NodeKind {
+    Dir(Vec<NodeId>),
+    File,
+}
+
+pub struct Node {
+    pub name: String,
+    pub parent: Option<NodeId>,
+    pub kind: NodeKind,
+    // for directories the cumulative size of everything below them
+    pub size: usize,
+    pub removed: bool,
+}
+
+// A directory tree stored in a flat arena. Nodes refer to each other by index
+// and the size of every directory is kept up to date as files are added, so
+// that querying it never needs to walk the tree.
+pub struct FileSystem {
+    nodes: Vec<Node>,
+}
+
+impl FileSystem {
+    pub fn new() -> FileSystem {
+        FileSystem {
+            nodes: vec![Node {
+                name: String::from("/"),
+                parent: None,
+                kind: NodeKind::Dir(Vec::new()),
+                size: 0,
+                removed: false,
+            }],
+        }
+    }
+
+    // number of slots in the arena, including removed nodes, so that it can
+    // be used to size tables indexed by NodeId
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn root(&self) -> NodeId {
+        0
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    pub fn is_dir(&self, id: NodeId) -> bool {
+        matches!(self.nodes[id].kind, NodeKind::Dir(_))
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id].parent
+    }
+
+    pub fn size(&self, id: NodeId) -> usize {
+        self.nodes[id].size
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        match &self.nodes[id].kind {
+            NodeKind::Dir(children) => children,
+            NodeKind::File => &[],
+        }
+    }
+
+    pub fn child(&self, dir: NodeId, name: &str) -> Option<NodeId> {
+        self.children(dir)
+            .iter()
+            .copied()
+            .find(|&c| self.nodes[c].name == name)
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = NodeId> + '_ {
+        (0..self.nodes.len()).filter(|&id| !self.nodes[id].removed)
+    }
+
+    pub fn dirs(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.ids().filter(|&id| self.is_dir(id))
+    }
+
+    // Looks up a path relative to `from`, or absolute if it starts with /.
+    pub fn resolve(&self, from: NodeId, path: &str) -> Option<NodeId> {
+        let mut id = if path.starts_with('/') { self.root() } else { from };
+        for seg in path.split('/') {
+            match seg {
+                "" | "." => {}
+                ".." => id = self.parent(id).unwrap_or(id),
+                name => id = self.child(id, name)?,
+            }
+        }
+        Some(id)
+    }
+
+    pub fn path(&self, id: NodeId) -> String {
+        match self.nodes[id].parent {
+            None => String::from("/"),
+            Some(p) if p == self.root() => format!("/{}", self.nodes[id].name),
+            Some(p) => format!("{}/{}", self.path(p), self.nodes[id].name),
+        }
+    }
+
+    fn add(&mut self, parent: NodeId, name: &str, kind: NodeKind, size: usize) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            name: String::from(name),
+            parent: Some(parent),
+            kind,
+            size,
+            removed: false,
+        });
+        match &mut self.nodes[parent].kind {
+            NodeKind::Dir(children) => children.push(id),
+            NodeKind::File => panic!("parent of {name} is not a directory"),
+        }
+        id
+    }
+
+    pub fn add_dir(&mut self, parent: NodeId, name: &str) -> NodeId {
+        self.add(parent, name, NodeKind::Dir(Vec::new()), 0)
+    }
+
+    pub fn add_file(&mut self, parent: NodeId, name: &str, size: usize) -> NodeId {
+        let id = self.add(parent, name, NodeKind::File, 0);
+        self.resize_file(id, size);
+        id
+    }
+
+    // removes a file or a directory with everything in it
+    pub fn remove(&mut self, id: NodeId) {
+        let Some(parent) = self.nodes[id].parent else {
+            panic!("can't remove the root directory");
+        };
+        let size = self.nodes[id].size;
+        let mut dir = Some(parent);
+        while let Some(d) = dir {
+            self.nodes[d].size -= size;
+            dir = self.nodes[d].parent;
+        }
+        if let NodeKind::Dir(children) = &mut self.nodes[parent].kind {
+            children.retain(|&c| c != id);
+        }
+        let mut stack = vec![id];
+        while let Some(n) = stack.pop() {
+            self.nodes[n].removed = true;
+            stack.extend(self.children(n));
+        }
+    }
+
+    // changes the size of a file and of all directories containing it
+    pub fn resize_file(&mut self, id: NodeId, size: usize) {
+        let old = self.nodes[id].size;
+        let mut node = Some(id);
+        while let Some(n) = node {
+            self.nodes[n].size = self.nodes[n].size - old + size;
+            node = self.nodes[n].parent;
+        }
+    }
+}
@@ -1,137 +1,357 @@
-use std::cell::RefCell;
-use std::cmp;
+mod cleanup;
+mod diff;
+mod fs;
+mod query;
+mod render;
+mod repl;
+mod transcript;
+mod walk;
+
+use std::env;
 use std::io;
-use std::rc::Rc;
-use std::str::FromStr;
+use std::io::Read;
+use std::path::Path;
+use std::process;
 
-struct Directory {
-    name: String,
-    entries: Vec<ListEntry>,
-}
+use cleanup::Capacity;
+use cleanup::Objective;
+use fs::FileSystem;
+use render::SortBy;
+use render::TreeOptions;
 
-impl Directory {
-    fn total_size(&self) -> usize {
-        let mut total = 0;
-        for e in &self.entries {
-            match e {
-                ListEntry::File(f) => total += f.size,
-                ListEntry::Dir(d) => total += d.borrow().total_size(),
-            }
+fn load(input: &str) -> FileSystem {
+    let (fs, conflicts) = match transcript::parse(input) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("error: {e}");
+            process::exit(1);
         }
-        dbg!(&self.name);
-        dbg!(total);
-        total
+    };
+    for c in &conflicts {
+        eprintln!("warning: {c}");
     }
+    fs
+}
 
-    fn total_size_filt(&self, lim: usize) -> usize {
-        let mut total = 0;
-        for e in &self.entries {
-            if let ListEntry::Dir(d) = e {
-                let db = d.borrow();
-                let size = db.total_size();
-                if size < lim {
-                    total += size;
+fn read_stdin() -> String {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input).expect("failed to read stdin");
+    input
+}
+
+fn tree_options(args: &[String]) -> TreeOptions {
+    let mut opts = TreeOptions {
+        sort: SortBy::Name,
+        max_depth: None,
+        human: false,
+    };
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--sort" => {
+                opts.sort = match it.next().map(|s| s.as_str()) {
+                    Some("name") => SortBy::Name,
+                    Some("size") => SortBy::Size,
+                    _ => panic!("--sort needs name or size"),
                 }
-                total += db.total_size_filt(lim);
             }
+            "--depth" => {
+                let v = it.next().expect("--depth needs a value");
+                opts.max_depth = Some(v.parse().expect("failed to parse --depth"));
+            }
+            "-h" | "--human" => opts.human = true,
+            _ => panic!("unknown argument {arg}"),
         }
-        total
     }
+    opts
+}
 
-    fn smallest_larger(&self, lim: usize) -> Option<usize> {
-        let mut min = None;
-        for e in &self.entries {
-            if let ListEntry::Dir(d) = e {
-                let db = d.borrow();
-                if let Some(s) = db.smallest_larger(lim) {
-                    match min {
-                        None => min = Some(s),
-                        Some(m) => min = Some(cmp::min(m, s)),
-                    }
+struct CleanupOptions {
+    capacity: Capacity,
+    small_limit: usize,
+}
+
+fn cleanup_options(args: &[String]) -> CleanupOptions {
+    let mut opts = CleanupOptions {
+        capacity: Capacity {
+            disk: 70000000,
+            required: 30000000,
+        },
+        small_limit: 100000,
+    };
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let v = it
+            .next()
+            .unwrap_or_else(|| panic!("{arg} needs a value"))
+            .parse()
+            .unwrap_or_else(|_| panic!("failed to parse {arg}"));
+        match arg.as_str() {
+            "--disk-size" => opts.capacity.disk = v,
+            "--required" => opts.capacity.required = v,
+            "--small-limit" => opts.small_limit = v,
+            _ => panic!("unknown argument {arg}"),
+        }
+    }
+    opts
+}
+
+fn to_be_freed(fs: &FileSystem, opts: &CleanupOptions) -> usize {
+    match opts.capacity.to_be_freed(fs) {
+        Ok(n) => n,
+        Err(e) => {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+    }
+}
+
+fn report(fs: &FileSystem, opts: &CleanupOptions) {
+    let used = fs.size(fs.root());
+    let need = to_be_freed(fs, opts);
+    println!("disk size:      {:>12}", opts.capacity.disk);
+    println!("used:           {:>12}", used);
+    println!("free:           {:>12}", opts.capacity.disk - used);
+    println!("required free:  {:>12}", opts.capacity.required);
+    println!("to be freed:    {:>12}", need);
+
+    let small = cleanup::small_dirs(fs, opts.small_limit);
+    let total: usize = small.iter().map(|&d| fs.size(d)).sum();
+    println!();
+    println!("directories smaller than {}: {} total", opts.small_limit, total);
+    for d in small {
+        println!("  {:>12}  {}", fs.size(d), fs.path(d));
+    }
+
+    println!();
+    if need == 0 {
+        println!("nothing needs to be deleted");
+        return;
+    }
+    let candidates = cleanup::candidates(fs, need);
+    match candidates.first() {
+        Some(&d) => println!("smallest directory to delete: {} ({})", fs.path(d), fs.size(d)),
+        None => println!("no single directory frees enough space"),
+    }
+    println!("candidates for deletion:");
+    for d in candidates {
+        println!("  {:>12}  {}", fs.size(d), fs.path(d));
+    }
+}
+
+// trees with up to this many entries are planned exactly in auto mode
+const EXACT_NODES: usize = 200;
+const EXACT_MAX_STATES: usize = 100000;
+
+fn plan(fs: &FileSystem, flags: &[String]) {
+    let mut objective = Objective::Bytes;
+    let mut mode = "auto";
+    let mut rest = Vec::new();
+    let mut it = flags.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--objective" => {
+                objective = match it.next().map(|s| s.as_str()) {
+                    Some("bytes") => Objective::Bytes,
+                    Some("items") => Objective::Items,
+                    _ => panic!("--objective needs bytes or items"),
                 }
-                let s = db.total_size();
-                if s > lim {
-                    match min {
-                        None => min = Some(s),
-                        Some(m) => min = Some(cmp::min(m, s)),
-                    }
+            }
+            "--mode" => {
+                mode = match it.next().map(|s| s.as_str()) {
+                    Some(m @ ("auto" | "exact" | "heuristic")) => m,
+                    _ => panic!("--mode needs auto, exact or heuristic"),
                 }
             }
+            _ => rest.push(arg.clone()),
         }
-        min
+    }
+    let opts = cleanup_options(&rest);
+    let need = to_be_freed(fs, &opts);
+    if need == 0 {
+        println!("nothing needs to be deleted");
+        return;
+    }
+
+    let exact = match mode {
+        "exact" => true,
+        "heuristic" => false,
+        _ => fs.ids().count() <= EXACT_NODES,
+    };
+    let mut plan = None;
+    if exact {
+        match cleanup::plan_exact(fs, need, objective, EXACT_MAX_STATES) {
+            Some(p) => plan = Some(p),
+            None if mode == "exact" => {
+                eprintln!("error: tree too large for an exact plan");
+                process::exit(1);
+            }
+            None => eprintln!("warning: tree too large for an exact plan, using heuristic"),
+        }
+    }
+    let plan = plan.unwrap_or_else(|| cleanup::plan_heuristic(fs, need, objective));
+    let Some(plan) = plan else {
+        eprintln!("error: deleting everything frees less than {need} bytes");
+        process::exit(1);
+    };
+
+    println!(
+        "{} plan: delete {} entries freeing {} bytes (needed {})",
+        if plan.exact { "exact" } else { "heuristic" },
+        plan.nodes.len(),
+        plan.freed,
+        need
+    );
+    let mut nodes = plan.nodes;
+    nodes.sort_by_key(|&id| fs.path(id));
+    for id in nodes {
+        let kind = if fs.is_dir(id) { "dir" } else { "file" };
+        println!("  {:>12}  {:<4}  {}", fs.size(id), kind, fs.path(id));
     }
 }
 
-struct File {
-    name: String,
-    size: usize,
+fn signed(old: usize, new: usize) -> String {
+    if new >= old {
+        format!("+{}", new - old)
+    } else {
+        format!("-{}", old - new)
+    }
 }
 
-enum ListEntry {
-    Dir(Rc<RefCell<Directory>>),
-    File(File),
+fn print_diff(old: &FileSystem, new: &FileSystem) {
+    let kind = |fs: &FileSystem, id| if fs.is_dir(id) { "dir" } else { "file" };
+    let mut dirs = Vec::new();
+    for c in diff::diff(old, new) {
+        match c {
+            diff::Change::Added(id) => {
+                println!("+ {:<4}  {:>12}  {}", kind(new, id), new.size(id), new.path(id))
+            }
+            diff::Change::Removed(id) => {
+                println!("- {:<4}  {:>12}  {}", kind(old, id), old.size(id), old.path(id))
+            }
+            diff::Change::Resized(a, b) => println!(
+                "~ file  {:>12}  {} ({} -> {})",
+                signed(old.size(a), new.size(b)),
+                new.path(b),
+                old.size(a),
+                new.size(b)
+            ),
+            diff::Change::DirSize(a, b) => dirs.push((a, b)),
+        }
+    }
+    if dirs.is_empty() {
+        return;
+    }
+    println!();
+    println!("directory size changes:");
+    for (a, b) in dirs {
+        println!(
+            "  {:>12}  {} ({} -> {})",
+            signed(old.size(a), new.size(b)),
+            new.path(b),
+            old.size(a),
+            new.size(b)
+        );
+    }
 }
 
-impl FromStr for ListEntry {
-    type Err = ();
+fn dir_arg(flags: &[String]) -> &Path {
+    match flags {
+        [dir] => Path::new(dir),
+        _ => panic!("expected a single directory argument"),
+    }
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (s1, s2) = s.split_once(" ").ok_or(())?;
-        if s1 == "dir" {
-            Ok(ListEntry::Dir(Rc::new(RefCell::new(Directory {
-                name: String::from(s2),
-                entries: Vec::new(),
-            }))))
-        } else {
-            let size: usize = s1.parse().map_err(|_| ())?;
-            Ok(ListEntry::File(File {
-                name: String::from(s2),
-                size: size,
-            }))
+fn verify(dir: &Path) {
+    let fs = load(&walk::transcript(dir));
+    match walk::verify(&fs, dir) {
+        Ok(mismatches) if mismatches.is_empty() => {
+            println!("{} directories match du", fs.dirs().count());
+        }
+        Ok(mismatches) => {
+            for m in mismatches {
+                println!("{m}");
+            }
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            process::exit(1);
         }
     }
 }
 
 fn main() {
-    let root = Rc::new(RefCell::new(Directory {
-        name: String::from("/"),
-        entries: Vec::new(),
-    }));
-    let mut pwd = root.clone();
-    let mut parents = Vec::new();
-    let lines = io::stdin().lines();
-    for it in lines {
-        let l = it.unwrap();
-        if let Some(r) = l.trim().strip_prefix("$ ") {
-            if let Some(dir) = r.strip_prefix("cd ") {
-                if dir == ".." {
-                    pwd = parents.pop().unwrap();
-                } else if dir == "/" {
-                    parents.clear();
-                    pwd = root.clone();
-                } else {
-                    let mut newpwd = None;
-                    for e in &pwd.borrow().entries {
-                        if let ListEntry::Dir(d) = e {
-                            if d.borrow().name == dir {
-                                parents.push(pwd.clone());
-                                newpwd = Some(d.clone());
-                                break;
-                            }
-                        }
-                    }
-                    pwd = newpwd.expect("changed into directory {dir} that wasn't in listing");
+    let args: Vec<String> = env::args().skip(1).collect();
+    // without a command the flags are the cleanup options
+    let (cmd, flags) = match args.first() {
+        Some(a) if !a.starts_with('-') => (Some(a.as_str()), &args[1..]),
+        _ => (None, &args[..]),
+    };
+    match cmd {
+        Some("generate") => {
+            print!("{}", walk::transcript(dir_arg(flags)));
+            return;
+        }
+        Some("verify") => {
+            verify(dir_arg(flags));
+            return;
+        }
+        Some("diff") => {
+            let [old, new] = flags else {
+                panic!("diff needs two transcript files");
+            };
+            let read = |p: &String| std::fs::read_to_string(p).expect("failed to read transcript");
+            print_diff(&load(&read(old)), &load(&read(new)));
+            return;
+        }
+        Some("repl") => {
+            let Some((path, rest)) = flags.split_first() else {
+                panic!("repl needs a transcript file");
+            };
+            let input = std::fs::read_to_string(path).expect("failed to read transcript");
+            let opts = cleanup_options(rest);
+            repl::run(load(&input), &opts.capacity, &mut io::stdin().lock(), &mut io::stdout())
+                .expect("failed to run repl");
+            return;
+        }
+        _ => {}
+    }
+
+    let fs = load(&read_stdin());
+    match cmd {
+        None => {
+            let opts = cleanup_options(flags);
+            let need = to_be_freed(&fs, &opts);
+            if need == 0 {
+                // nothing has to be deleted
+                println!("0");
+                return;
+            }
+            match cleanup::candidates(&fs, need).first() {
+                Some(&d) => println!("{}", fs.size(d)),
+                None => {
+                    eprintln!("error: no single directory frees {need} bytes");
+                    process::exit(1);
+                }
+            }
+        }
+        Some("report") => report(&fs, &cleanup_options(flags)),
+        Some("tree") => print!("{}", render::tree(&fs, fs.root(), &tree_options(flags))),
+        Some("json") => println!("{}", render::json(&fs, fs.root())),
+        Some("plan") => plan(&fs, flags),
+        Some("find") => {
+            let e = match query::parse(flags) {
+                Ok(e) => e,
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    process::exit(1);
                 }
+            };
+            for id in query::find(&fs, &e) {
+                println!("{:>12}  {}", fs.size(id), fs.path(id));
             }
-            // ignore ls and assume everything without a $ is part of a listing
-        } else {
-            let e = l.trim().parse().unwrap();
-            pwd.borrow_mut().entries.push(e);
         }
+        Some(cmd) => panic!("unknown command {cmd}"),
     }
-    let total = root.borrow().total_size();
-    let unused = 70000000 - total;
-    let to_be_freed = 30000000 - unused;
-    let smallest = root.borrow().smallest_larger(to_be_freed).unwrap();
-    println!("{smallest}");
 }
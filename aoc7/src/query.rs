@@ -0,0 +1,244 @@
+use std::fmt;
+
+use crate::fs::FileSystem;
+use crate::fs::NodeId;
+use crate::render;
+use crate::render::SortBy;
+
+#[derive(Clone, Copy)]
+pub enum Cmp {
+    Less,
+    Equal,
+    Greater,
+}
+
+impl Cmp {
+    fn eval(self, a: usize, b: usize) -> bool {
+        match self {
+            Cmp::Less => a < b,
+            Cmp::Equal => a == b,
+            Cmp::Greater => a > b,
+        }
+    }
+}
+
+pub enum Expr {
+    True,
+    Name(String),
+    Size(Cmp, usize),
+    Depth(Cmp, usize),
+    Path(String),
+    IsDir(bool),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug)]
+pub struct QueryError {
+    pub descr: &'static str,
+    pub token: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at: {})", self.descr, self.token)
+    }
+}
+
+// Matches `*`, `?` and `[...]` character classes, with `[!...]` negating.
+pub fn glob(pattern: &str, name: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    glob_at(&p, &n)
+}
+
+fn glob_at(p: &[char], n: &[char]) -> bool {
+    match p.first() {
+        None => n.is_empty(),
+        Some('*') => (0..=n.len()).any(|i| glob_at(&p[1..], &n[i..])),
+        Some('?') => !n.is_empty() && glob_at(&p[1..], &n[1..]),
+        Some('[') => {
+            let Some(end) = p.iter().skip(2).position(|&c| c == ']').map(|i| i + 2) else {
+                return n.first() == Some(&'[') && glob_at(&p[1..], &n[1..]);
+            };
+            let Some(&c) = n.first() else {
+                return false;
+            };
+            let (negate, class) = match p[1] {
+                '!' => (true, &p[2..end]),
+                _ => (false, &p[1..end]),
+            };
+            let mut found = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    found |= class[i] <= c && c <= class[i + 2];
+                    i += 3;
+                } else {
+                    found |= class[i] == c;
+                    i += 1;
+                }
+            }
+            found != negate && glob_at(&p[end + 1..], &n[1..])
+        }
+        Some(&c) => n.first() == Some(&c) && glob_at(&p[1..], &n[1..]),
+    }
+}
+
+// Parses +N, -N and N like find does, with an optional K, M or G suffix.
+fn parse_number(s: &str, suffixes: bool) -> Option<(Cmp, usize)> {
+    let (cmp, rest) = match s.as_bytes().first()? {
+        b'+' => (Cmp::Greater, &s[1..]),
+        b'-' => (Cmp::Less, &s[1..]),
+        _ => (Cmp::Equal, s),
+    };
+    let (digits, mult) = match rest.as_bytes().last()? {
+        b'k' | b'K' if suffixes => (&rest[..rest.len() - 1], 1 << 10),
+        b'M' if suffixes => (&rest[..rest.len() - 1], 1 << 20),
+        b'G' if suffixes => (&rest[..rest.len() - 1], 1 << 30),
+        _ => (rest, 1),
+    };
+    let n: usize = digits.parse().ok()?;
+    Some((cmp, n.checked_mul(mult)?))
+}
+
+struct Parser<'a> {
+    args: &'a [String],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.args.get(self.pos).map(|s| s.as_str())
+    }
+
+    fn err(&self, descr: &'static str) -> QueryError {
+        QueryError {
+            descr,
+            token: String::from(self.peek().unwrap_or("end of query")),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, QueryError> {
+        let mut e = self.and()?;
+        while let Some("-o" | "-or") = self.peek() {
+            self.pos += 1;
+            e = Expr::Or(Box::new(e), Box::new(self.and()?));
+        }
+        Ok(e)
+    }
+
+    // adjacent predicates are implicitly combined with and
+    fn and(&mut self) -> Result<Expr, QueryError> {
+        let mut e = self.not()?;
+        loop {
+            match self.peek() {
+                None | Some("-o" | "-or" | ")") => return Ok(e),
+                Some("-a" | "-and") => self.pos += 1,
+                _ => {}
+            }
+            e = Expr::And(Box::new(e), Box::new(self.not()?));
+        }
+    }
+
+    fn not(&mut self) -> Result<Expr, QueryError> {
+        if let Some("!" | "-not") = self.peek() {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, QueryError> {
+        let Some(tok) = self.peek() else {
+            return Err(self.err("expected a predicate"));
+        };
+        if tok == "(" {
+            self.pos += 1;
+            let e = self.or()?;
+            if self.peek() != Some(")") {
+                return Err(self.err("expected )"));
+            }
+            self.pos += 1;
+            return Ok(e);
+        }
+        let pred = match tok {
+            "-name" | "-size" | "-depth" | "-path" | "-type" => String::from(tok),
+            _ => return Err(self.err("unknown predicate")),
+        };
+        self.pos += 1;
+        let Some(arg) = self.peek() else {
+            return Err(self.err("missing argument"));
+        };
+        let e = match pred.as_str() {
+            "-name" => Expr::Name(String::from(arg)),
+            "-path" => Expr::Path(String::from(arg.trim_end_matches('/'))),
+            "-size" => {
+                let (cmp, n) = parse_number(arg, true).ok_or_else(|| self.err("invalid size"))?;
+                Expr::Size(cmp, n)
+            }
+            "-depth" => {
+                let (cmp, n) = parse_number(arg, false).ok_or_else(|| self.err("invalid depth"))?;
+                Expr::Depth(cmp, n)
+            }
+            _ => match arg {
+                "d" => Expr::IsDir(true),
+                "f" => Expr::IsDir(false),
+                _ => return Err(self.err("type must be d or f")),
+            },
+        };
+        self.pos += 1;
+        Ok(e)
+    }
+}
+
+// Parses a find-style expression such as
+// `-name '*.log' -size +1M -o ( -type d -depth -2 )`.
+pub fn parse(args: &[String]) -> Result<Expr, QueryError> {
+    if args.is_empty() {
+        return Ok(Expr::True);
+    }
+    let mut p = Parser { args, pos: 0 };
+    let e = p.or()?;
+    if p.pos != args.len() {
+        return Err(p.err("unexpected argument"));
+    }
+    Ok(e)
+}
+
+fn matches(fs: &FileSystem, id: NodeId, depth: usize, e: &Expr) -> bool {
+    match e {
+        Expr::True => true,
+        Expr::Name(pattern) => glob(pattern, &fs.node(id).name),
+        Expr::Size(cmp, n) => cmp.eval(fs.size(id), *n),
+        Expr::Depth(cmp, n) => cmp.eval(depth, *n),
+        Expr::Path(prefix) => {
+            let path = fs.path(id);
+            prefix.is_empty()
+                || path == *prefix
+                || path.strip_prefix(prefix.as_str()).is_some_and(|r| r.starts_with('/'))
+        }
+        Expr::IsDir(dir) => fs.is_dir(id) == *dir,
+        Expr::Not(e) => !matches(fs, id, depth, e),
+        Expr::And(a, b) => matches(fs, id, depth, a) && matches(fs, id, depth, b),
+        Expr::Or(a, b) => matches(fs, id, depth, a) || matches(fs, id, depth, b),
+    }
+}
+
+fn collect(fs: &FileSystem, id: NodeId, depth: usize, e: &Expr, out: &mut Vec<NodeId>) {
+    if matches(fs, id, depth, e) {
+        out.push(id);
+    }
+    for c in render::sorted_children(fs, id, SortBy::Name) {
+        collect(fs, c, depth + 1, e, out);
+    }
+}
+
+// All entries matching `e` in the order find would list them. The root has
+// depth 0.
+pub fn find(fs: &FileSystem, e: &Expr) -> Vec<NodeId> {
+    let mut out = Vec::new();
+    collect(fs, fs.root(), 0, e, &mut out);
+    out
+}
@@ -0,0 +1,122 @@
+use crate::fs::FileSystem;
+use crate::fs::NodeId;
+
+#[derive(Clone, Copy)]
+pub enum SortBy {
+    Name,
+    // largest first
+    Size,
+}
+
+pub struct TreeOptions {
+    pub sort: SortBy,
+    pub max_depth: Option<usize>,
+    pub human: bool,
+}
+
+// Formats sizes like du -h, with powers of 1024 and one decimal for small
+// values.
+pub fn human(size: usize) -> String {
+    const UNITS: [&str; 6] = ["", "K", "M", "G", "T", "P"];
+    let mut s = size as f64;
+    let mut unit = 0;
+    while s >= 1024.0 && unit < UNITS.len() - 1 {
+        s /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}", size)
+    } else if s < 10.0 {
+        format!("{:.1}{}", s, UNITS[unit])
+    } else {
+        format!("{:.0}{}", s, UNITS[unit])
+    }
+}
+
+fn format_size(size: usize, human_readable: bool) -> String {
+    if human_readable {
+        human(size)
+    } else {
+        size.to_string()
+    }
+}
+
+pub fn sorted_children(fs: &FileSystem, dir: NodeId, sort: SortBy) -> Vec<NodeId> {
+    let mut children = fs.children(dir).to_vec();
+    match sort {
+        SortBy::Name => children.sort_by(|&a, &b| fs.node(a).name.cmp(&fs.node(b).name)),
+        SortBy::Size => children.sort_by(|&a, &b| {
+            fs.size(b)
+                .cmp(&fs.size(a))
+                .then_with(|| fs.node(a).name.cmp(&fs.node(b).name))
+        }),
+    }
+    children
+}
+
+fn tree_node(fs: &FileSystem, id: NodeId, opts: &TreeOptions, prefix: &str, depth: usize, out: &mut String) {
+    if opts.max_depth.is_some_and(|max| depth >= max) {
+        return;
+    }
+    let children = sorted_children(fs, id, opts.sort);
+    for (i, &c) in children.iter().enumerate() {
+        let last = i == children.len() - 1;
+        let node = fs.node(c);
+        let name = if fs.is_dir(c) { format!("{}/", node.name) } else { node.name.clone() };
+        out.push_str(&format!(
+            "{}{} {} ({})\n",
+            prefix,
+            if last { "└──" } else { "├──" },
+            name,
+            format_size(fs.size(c), opts.human)
+        ));
+        if fs.is_dir(c) {
+            let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            tree_node(fs, c, opts, &prefix, depth + 1, out);
+        }
+    }
+}
+
+// Renders the tree below `id` with the cumulative size of every entry.
+pub fn tree(fs: &FileSystem, id: NodeId, opts: &TreeOptions) -> String {
+    let mut out = format!("{} ({})\n", fs.path(id), format_size(fs.size(id), opts.human));
+    tree_node(fs, id, opts, "", 0, &mut out);
+    out
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+pub fn json(fs: &FileSystem, id: NodeId) -> String {
+    let node = fs.node(id);
+    if !fs.is_dir(id) {
+        return format!(
+            "{{\"name\":{},\"type\":\"file\",\"size\":{}}}",
+            json_string(&node.name),
+            node.size
+        );
+    }
+    let children: Vec<String> = sorted_children(fs, id, SortBy::Name)
+        .iter()
+        .map(|&c| json(fs, c))
+        .collect();
+    format!(
+        "{{\"name\":{},\"type\":\"dir\",\"size\":{},\"children\":[{}]}}",
+        json_string(&node.name),
+        node.size,
+        children.join(",")
+    )
+}
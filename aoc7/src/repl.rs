@@ -0,0 +1,142 @@
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+
+use crate::cleanup::Capacity;
+use crate::fs::FileSystem;
+use crate::fs::NodeId;
+use crate::render;
+use crate::render::SortBy;
+
+const HELP: &str = "\
+cd PATH    change directory
+ls [PATH]  list a directory with cumulative sizes
+du [PATH]  show the size of a directory and everything below it
+rm PATH    delete a file or directory
+df         show disk usage
+pwd        print the current directory
+exit       leave";
+
+struct Repl<'a> {
+    fs: FileSystem,
+    pwd: NodeId,
+    capacity: &'a Capacity,
+}
+
+impl Repl<'_> {
+    fn resolve(&self, path: Option<&str>) -> Result<NodeId, String> {
+        let path = path.unwrap_or(".");
+        self.fs
+            .resolve(self.pwd, path)
+            .ok_or_else(|| format!("{path}: no such file or directory"))
+    }
+
+    fn ls(&self, id: NodeId, out: &mut dyn Write) -> io::Result<()> {
+        if !self.fs.is_dir(id) {
+            return writeln!(out, "{:>12}  {}", self.fs.size(id), self.fs.node(id).name);
+        }
+        for c in render::sorted_children(&self.fs, id, SortBy::Name) {
+            let node = self.fs.node(c);
+            let kind = if self.fs.is_dir(c) { "dir" } else { "" };
+            writeln!(out, "{:>12}  {:<3}  {}", node.size, kind, node.name)?;
+        }
+        Ok(())
+    }
+
+    // like du, directories are listed after everything in them
+    fn du(&self, id: NodeId, out: &mut dyn Write) -> io::Result<()> {
+        for &c in self.fs.children(id) {
+            if self.fs.is_dir(c) {
+                self.du(c, out)?;
+            }
+        }
+        writeln!(out, "{:>12}  {}", self.fs.size(id), self.fs.path(id))
+    }
+
+    fn df(&self, out: &mut dyn Write) -> io::Result<()> {
+        let used = self.fs.size(self.fs.root());
+        writeln!(out, "{:>12}  disk size", self.capacity.disk)?;
+        writeln!(out, "{:>12}  used", used)?;
+        match self.capacity.disk.checked_sub(used) {
+            Some(free) => writeln!(out, "{:>12}  free", free)?,
+            None => writeln!(out, "{:>12}  over capacity", used - self.capacity.disk)?,
+        }
+        match self.capacity.to_be_freed(&self.fs) {
+            Ok(need) => writeln!(out, "{:>12}  still to be freed", need),
+            Err(e) => writeln!(out, "{e}"),
+        }
+    }
+
+    // returns false when the session should end
+    fn command(&mut self, line: &str, out: &mut dyn Write) -> io::Result<bool> {
+        let mut words = line.split_whitespace();
+        let Some(cmd) = words.next() else {
+            return Ok(true);
+        };
+        let arg = words.next();
+        if words.next().is_some() {
+            writeln!(out, "{cmd}: too many arguments")?;
+            return Ok(true);
+        }
+        let res = match (cmd, arg) {
+            ("exit" | "quit", None) => return Ok(false),
+            ("help", None) => writeln!(out, "{HELP}").map_err(|e| e.to_string()),
+            ("pwd", None) => writeln!(out, "{}", self.fs.path(self.pwd)).map_err(|e| e.to_string()),
+            ("df", None) => self.df(out).map_err(|e| e.to_string()),
+            ("cd", _) => self.resolve(Some(arg.unwrap_or("/"))).and_then(|id| {
+                if !self.fs.is_dir(id) {
+                    return Err(format!("{}: not a directory", arg.unwrap()));
+                }
+                self.pwd = id;
+                Ok(())
+            }),
+            ("ls", _) => self
+                .resolve(arg)
+                .and_then(|id| self.ls(id, out).map_err(|e| e.to_string())),
+            ("du", _) => self
+                .resolve(arg)
+                .and_then(|id| self.du(id, out).map_err(|e| e.to_string())),
+            ("rm", Some(path)) => self.resolve(Some(path)).and_then(|id| {
+                if id == self.fs.root() {
+                    return Err(String::from("refusing to remove /"));
+                }
+                // leave a removed working directory like a shell would
+                let mut p = Some(self.pwd);
+                while let Some(d) = p {
+                    if d == id {
+                        self.pwd = self.fs.parent(id).unwrap();
+                    }
+                    p = self.fs.parent(d);
+                }
+                self.fs.remove(id);
+                Ok(())
+            }),
+            _ => Err(format!("{cmd}: unknown command or wrong arguments, try help")),
+        };
+        if let Err(e) = res {
+            writeln!(out, "{e}")?;
+        }
+        Ok(true)
+    }
+}
+
+// Reads commands from `input` until it ends or exit is given.
+pub fn run(fs: FileSystem, capacity: &Capacity, input: &mut dyn BufRead, out: &mut dyn Write) -> io::Result<()> {
+    let mut repl = Repl {
+        pwd: fs.root(),
+        fs,
+        capacity,
+    };
+    loop {
+        write!(out, "{}$ ", repl.fs.path(repl.pwd))?;
+        out.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            writeln!(out)?;
+            return Ok(());
+        }
+        if !repl.command(line.trim(), out)? {
+            return Ok(());
+        }
+    }
+}
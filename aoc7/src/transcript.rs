@@ -0,0 +1,181 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::fs::FileSystem;
+use crate::fs::NodeId;
+use crate::fs::NodeKind;
+
+pub struct File {
+    pub name: String,
+    pub size: usize,
+}
+
+pub enum ListEntry {
+    Dir(String),
+    File(File),
+}
+
+impl FromStr for ListEntry {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (s1, s2) = s.split_once(' ').ok_or(())?;
+        if s1 == "dir" {
+            Ok(ListEntry::Dir(String::from(s2)))
+        } else {
+            let size: usize = s1.parse().map_err(|_| ())?;
+            Ok(ListEntry::File(File {
+                name: String::from(s2),
+                size,
+            }))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TranscriptError {
+    pub line: usize,
+    pub descr: &'static str,
+    pub input: String,
+}
+
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} (input: {})", self.line, self.descr, self.input)
+    }
+}
+
+// Something that doesn't fit what was seen earlier in the transcript, for
+// example a name that is a file in one listing and a directory in another. The
+// first version of an entry wins, except for file sizes, where the latest
+// listing is taken.
+pub struct Conflict {
+    pub line: usize,
+    pub path: String,
+    pub descr: String,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} {}", self.line, self.path, self.descr)
+    }
+}
+
+struct Interpreter {
+    fs: FileSystem,
+    pwd: NodeId,
+    // the directory whose listing is currently being read
+    listing: Option<NodeId>,
+    conflicts: Vec<Conflict>,
+}
+
+impl Interpreter {
+    fn cd(&mut self, path: &str, line: usize, input: &str) -> Result<(), TranscriptError> {
+        let mut dir = if path.starts_with('/') { self.fs.root() } else { self.pwd };
+        for seg in path.split('/') {
+            match seg {
+                "" | "." => {}
+                // like in a real shell, .. in the root stays there
+                ".." => dir = self.fs.parent(dir).unwrap_or(dir),
+                name => {
+                    dir = match self.fs.child(dir, name) {
+                        Some(c) if self.fs.is_dir(c) => c,
+                        Some(_) => {
+                            return Err(TranscriptError {
+                                line,
+                                descr: "changed into a file",
+                                input: String::from(input),
+                            })
+                        }
+                        // directories don't have to be listed before
+                        // changing into them
+                        None => self.fs.add_dir(dir, name),
+                    }
+                }
+            }
+        }
+        self.pwd = dir;
+        Ok(())
+    }
+
+    fn conflict(&mut self, line: usize, id: NodeId, descr: String) {
+        self.conflicts.push(Conflict {
+            line,
+            path: self.fs.path(id),
+            descr,
+        });
+    }
+
+    fn entry(&mut self, dir: NodeId, e: ListEntry, line: usize) {
+        let name = match &e {
+            ListEntry::Dir(name) => name,
+            ListEntry::File(f) => &f.name,
+        };
+        let Some(existing) = self.fs.child(dir, name) else {
+            match e {
+                ListEntry::Dir(name) => self.fs.add_dir(dir, &name),
+                ListEntry::File(f) => self.fs.add_file(dir, &f.name, f.size),
+            };
+            return;
+        };
+        match (&self.fs.node(existing).kind, e) {
+            (NodeKind::Dir(_), ListEntry::Dir(_)) => {}
+            (NodeKind::File, ListEntry::File(f)) => {
+                let old = self.fs.size(existing);
+                if old != f.size {
+                    self.conflict(line, existing, format!("changed size from {} to {}", old, f.size));
+                    self.fs.resize_file(existing, f.size);
+                }
+            }
+            (NodeKind::Dir(_), ListEntry::File(_)) => {
+                self.conflict(line, existing, String::from("is a directory but was listed as a file"))
+            }
+            (NodeKind::File, ListEntry::Dir(_)) => {
+                self.conflict(line, existing, String::from("is a file but was listed as a directory"))
+            }
+        }
+    }
+
+    fn line(&mut self, l: &str, line: usize) -> Result<(), TranscriptError> {
+        let err = |descr| TranscriptError {
+            line,
+            descr,
+            input: String::from(l),
+        };
+        if let Some(cmd) = l.strip_prefix("$ ") {
+            self.listing = None;
+            if let Some(path) = cmd.strip_prefix("cd ") {
+                return self.cd(path.trim(), line, l);
+            }
+            if cmd.trim() != "ls" {
+                return Err(err("unknown command"));
+            }
+            self.listing = Some(self.pwd);
+            return Ok(());
+        }
+        let dir = self.listing.ok_or_else(|| err("output outside of ls"))?;
+        let e = l.parse().map_err(|_| err("invalid listing entry"))?;
+        self.entry(dir, e, line);
+        Ok(())
+    }
+}
+
+// Replays a transcript of cd and ls commands and their output, reconstructing
+// the filesystem that was explored.
+pub fn parse(input: &str) -> Result<(FileSystem, Vec<Conflict>), TranscriptError> {
+    let fs = FileSystem::new();
+    let mut it = Interpreter {
+        pwd: fs.root(),
+        fs,
+        listing: None,
+        conflicts: Vec::new(),
+    };
+    for (i, l) in input.lines().enumerate() {
+        let l = l.trim();
+        if l.is_empty() {
+            continue;
+        }
+        it.line(l, i + 1)?;
+    }
+    Ok((it.fs, it.conflicts))
+}
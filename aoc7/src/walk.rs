@@ -0,0 +1,183 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use crate::fs::FileSystem;
+use crate::fs::NodeId;
+
+fn walk(dir: &Path, out: &mut String) {
+    let mut entries: Vec<(String, fs::Metadata)> = Vec::new();
+    match fs::read_dir(dir) {
+        Ok(rd) => {
+            for e in rd {
+                let e = match e {
+                    Ok(e) => e,
+                    Err(err) => {
+                        eprintln!("warning: {}: {}", dir.display(), err);
+                        continue;
+                    }
+                };
+                let Some(name) = e.file_name().to_str().map(String::from) else {
+                    eprintln!("warning: {}: skipping name that isn't UTF-8", e.path().display());
+                    continue;
+                };
+                if name.contains('\n') {
+                    eprintln!("warning: {}: skipping name with a newline", e.path().display());
+                    continue;
+                }
+                // transcript lines are trimmed when they are parsed
+                if name.trim() != name {
+                    eprintln!(
+                        "warning: {}: skipping name with leading or trailing whitespace",
+                        e.path().display()
+                    );
+                    continue;
+                }
+                // like du, symlinks are not followed but count with their
+                // own size
+                match e.path().symlink_metadata() {
+                    Ok(m) => entries.push((name, m)),
+                    Err(err) => eprintln!("warning: {}: {}", e.path().display(), err),
+                }
+            }
+        }
+        Err(err) => eprintln!("warning: {}: {}", dir.display(), err),
+    }
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    out.push_str("$ ls\n");
+    for (name, m) in &entries {
+        if m.is_dir() {
+            out.push_str(&format!("dir {}\n", name));
+        } else {
+            out.push_str(&format!("{} {}\n", m.len(), name));
+        }
+    }
+    for (name, m) in &entries {
+        if m.is_dir() {
+            out.push_str(&format!("$ cd {}\n", name));
+            walk(&dir.join(name), out);
+            out.push_str("$ cd ..\n");
+        }
+    }
+}
+
+// Lists a directory on the local filesystem the way it would have been
+// explored in a device transcript.
+pub fn transcript(dir: &Path) -> String {
+    let mut out = String::from("$ cd /\n");
+    walk(dir, &mut out);
+    out
+}
+
+fn find(fs: &FileSystem, rel: &Path) -> Option<NodeId> {
+    let mut id = fs.root();
+    for c in rel.components() {
+        id = fs.child(id, c.as_os_str().to_str()?)?;
+    }
+    Some(id)
+}
+
+// du also counts the size of the directories themselves, which transcripts
+// don't have.
+fn dir_overhead(fs: &FileSystem, id: NodeId, path: &Path) -> io::Result<u64> {
+    let mut total = fs::symlink_metadata(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?
+        .len();
+    for &c in fs.children(id) {
+        if fs.is_dir(c) {
+            total += dir_overhead(fs, c, &path.join(&fs.node(c).name))?;
+        }
+    }
+    Ok(total)
+}
+
+// Compares the size of every directory in `fs`, which was generated from
+// `dir`, with what du reports for it. Returns the mismatches.
+pub fn verify(fs: &FileSystem, dir: &Path) -> io::Result<Vec<String>> {
+    let out = Command::new("du").arg("-b").arg("-l").arg(dir).output()?;
+    if !out.status.success() {
+        return Err(io::Error::other(format!(
+            "du failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    let mut mismatches = Vec::new();
+    for l in String::from_utf8_lossy(&out.stdout).lines() {
+        let Some((size, path)) = l.split_once('\t') else {
+            continue;
+        };
+        let du_size: u64 = size.parse().map_err(io::Error::other)?;
+        let path = Path::new(path);
+        let rel = path.strip_prefix(dir).map_err(io::Error::other)?;
+        let Some(id) = find(fs, rel) else {
+            mismatches.push(format!("{}: missing from transcript", path.display()));
+            continue;
+        };
+        // a directory that has gone missing or can't be read is reported
+        // like any other mismatch
+        let overhead = match dir_overhead(fs, id, path) {
+            Ok(n) => n,
+            Err(e) => {
+                mismatches.push(e.to_string());
+                continue;
+            }
+        };
+        let size = fs.size(id) as u64 + overhead;
+        if size != du_size {
+            mismatches.push(format!("{}: du says {}, transcript {}", path.display(), du_size, size));
+        }
+    }
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript;
+
+    #[test]
+    fn round_trip_matches_du() {
+        let dir = std::env::temp_dir().join(format!("aoc7-walk-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+        fs::create_dir_all(dir.join("empty")).unwrap();
+        fs::create_dir_all(dir.join("a/with space")).unwrap();
+        fs::write(dir.join("a/with space/file name"), vec![b'x'; 321]).unwrap();
+        fs::write(dir.join("top.txt"), vec![b'x'; 1234]).unwrap();
+        fs::write(dir.join("a/one"), vec![b'x'; 10]).unwrap();
+        fs::write(dir.join("a/b/two"), vec![b'x'; 70000]).unwrap();
+        fs::write(dir.join("a/b/zero"), b"").unwrap();
+
+        let (tree, conflicts) = transcript::parse(&transcript(&dir)).unwrap();
+        let mismatches = verify(&tree, &dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(conflicts.is_empty());
+        assert_eq!(tree.size(tree.root()), 1234 + 10 + 70000 + 321);
+        assert_eq!(mismatches.unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn names_the_transcript_cannot_carry_are_skipped() {
+        let dir = std::env::temp_dir().join(format!("aoc7-walk-trim-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join(" trailing ")).unwrap();
+        fs::write(dir.join(" trailing /f"), vec![b'x'; 100]).unwrap();
+        fs::write(dir.join("kept"), vec![b'x'; 7]).unwrap();
+
+        let text = transcript(&dir);
+        let (tree, _) = transcript::parse(&text).unwrap();
+        let mismatches = verify(&tree, &dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!text.contains("trailing"));
+        assert_eq!(tree.size(tree.root()), 7);
+        // du still lists the skipped directory and counts it in the root,
+        // which show as mismatches instead of an error
+        let mismatches = mismatches.unwrap();
+        assert_eq!(mismatches.len(), 2);
+        assert!(mismatches.iter().any(|m| m.ends_with(" trailing : missing from transcript")));
+    }
+}
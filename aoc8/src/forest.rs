@@ -0,0 +1,211 @@
+use std::collections::BTreeMap;
+
+use crate::line_through;
+use crate::score;
+use crate::sweep;
+use crate::viewing_distances;
+use crate::Distances;
+use crate::Grid;
+use crate::GridError;
+use crate::Score;
+
+// A grid together with everything the answers are computed from, kept up to
+// date while single trees change height. A change only affects the trees on
+// the lines through it, so only those lines are swept again.
+pub struct Forest {
+    pub grid: Grid,
+    dirs: &'static [(isize, isize)],
+    // bit `dir` is set when the tree can be seen from outside looking along
+    // the line in direction `dir`
+    seen: Vec<u8>,
+    visible: usize,
+    dist: Vec<Distances>,
+    pub scores: Vec<Score>,
+    // how many trees have each score, for the maximum
+    score_counts: BTreeMap<Score, usize>,
+}
+
+impl Forest {
+    pub fn new(grid: Grid, dirs: &'static [(isize, isize)]) -> Forest {
+        let dist = viewing_distances(&grid, dirs);
+        let scores: Vec<Score> = dist.iter().map(|d| score(d, dirs.len())).collect();
+        let mut f = Forest {
+            seen: vec![0; grid.cells.len()],
+            visible: 0,
+            dist,
+            scores,
+            score_counts: BTreeMap::new(),
+            grid,
+            dirs,
+        };
+        for &s in &f.scores {
+            *f.score_counts.entry(s).or_insert(0) += 1;
+        }
+        for (dir, &step) in dirs.iter().enumerate() {
+            for line in crate::lines(&f.grid, step) {
+                f.see_along(dir, &line);
+            }
+        }
+        f
+    }
+
+    pub fn visible(&self) -> usize {
+        self.visible
+    }
+
+    pub fn max_score(&self) -> Score {
+        self.score_counts.keys().next_back().copied().unwrap_or(0)
+    }
+
+    // Marks which trees on `line`, starting at the edge, can be seen looking
+    // along it.
+    fn see_along(&mut self, dir: usize, line: &[usize]) {
+        let mut max = None;
+        for &c in line {
+            let was = self.seen[c] != 0;
+            let h = self.grid.cells[c];
+            if max.is_none_or(|m| h > m) {
+                self.seen[c] |= 1 << dir;
+                max = Some(h);
+            } else {
+                self.seen[c] &= !(1 << dir);
+            }
+            match (was, self.seen[c] != 0) {
+                (false, true) => self.visible += 1,
+                (true, false) => self.visible -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    fn set_score(&mut self, c: usize, s: Score) {
+        let old = self.scores[c];
+        if old == s {
+            return;
+        }
+        if let Some(n) = self.score_counts.get_mut(&old) {
+            *n -= 1;
+            if *n == 0 {
+                self.score_counts.remove(&old);
+            }
+        }
+        *self.score_counts.entry(s).or_insert(0) += 1;
+        self.scores[c] = s;
+    }
+
+    pub fn set_height(&mut self, x: usize, y: usize, height: u8) {
+        assert!(
+            x < self.grid.width && y < self.grid.height,
+            "{x},{y} is outside the {}x{} grid",
+            self.grid.width,
+            self.grid.height
+        );
+        let c = y * self.grid.width + x;
+        if self.grid.cells[c] == height {
+            return;
+        }
+        self.grid.cells[c] = height;
+        let mut affected = Vec::new();
+        for (dir, &(dx, dy)) in self.dirs.iter().enumerate() {
+            self.see_along(dir, &line_through(&self.grid, c, (dx, dy)));
+            let back = line_through(&self.grid, c, (-dx, -dy));
+            sweep(&self.grid, &back, dir, &mut self.dist);
+            affected.extend(back);
+        }
+        affected.sort_unstable();
+        affected.dedup();
+        for t in affected {
+            self.set_score(t, score(&self.dist[t], self.dirs.len()));
+        }
+    }
+}
+
+pub struct Update {
+    pub x: usize,
+    pub y: usize,
+    pub height: u8,
+}
+
+// Parses a batch of updates, one `X Y HEIGHT` per line. Blank lines and lines
+// starting with # are skipped.
+pub fn parse_updates(input: &str, grid: &Grid) -> Result<Vec<Update>, GridError> {
+    let mut r = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = |descr| GridError {
+            line: i + 1,
+            descr,
+            input: String::from(line),
+        };
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [x, y, h] = fields[..] else {
+            return Err(err("expected X Y HEIGHT"));
+        };
+        let (Ok(x), Ok(y)) = (x.parse::<usize>(), y.parse::<usize>()) else {
+            return Err(err("invalid coordinate"));
+        };
+        if x >= grid.width || y >= grid.height {
+            return Err(err("outside the grid"));
+        }
+        let height = match h.parse::<u8>() {
+            Ok(h) if h <= 9 => h,
+            _ => return Err(err("height must be a digit")),
+        };
+        r.push(Update { x, y, height });
+    }
+    Ok(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::directions;
+    use crate::max_score;
+    use crate::scores;
+    use crate::tests::random_grid;
+    use crate::tests::Rng;
+    use crate::visible_trees;
+
+    // random logging and growth, compared with computing everything again
+    #[test]
+    fn updates_match_recompute() {
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+        for i in 0..100 {
+            let field = random_grid(&mut rng);
+            for dirs in [directions(false), directions(true)] {
+                let (width, height) = (field.width, field.height);
+                let mut forest = Forest::new(
+                    Grid {
+                        width,
+                        height,
+                        cells: field.cells.clone(),
+                    },
+                    dirs,
+                );
+                for u in 0..20 {
+                    let (x, y) = (rng.next(width), rng.next(height));
+                    forest.set_height(x, y, rng.next(10) as u8);
+                    let g = &forest.grid;
+                    assert!(
+                        forest.visible() == visible_trees(g, dirs)
+                            && forest.scores == scores(g, dirs)
+                            && forest.max_score() == max_score(g, dirs),
+                        "grid {i} ({width}x{height}): update {u} at {x},{y} in {} directions differs from a full recompute",
+                        dirs.len()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "outside")]
+    fn update_outside_the_grid() {
+        let grid = Grid::parse("123\n456\n").unwrap();
+        let mut forest = Forest::new(grid, directions(false));
+        forest.set_height(3, 0, 9);
+    }
+}
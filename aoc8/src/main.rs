@@ -1,141 +1,744 @@
+mod forest;
+mod render;
+mod sight;
+
+use std::cmp::Reverse;
+use std::env;
+use std::fmt;
+use std::fs;
 use std::io;
+use std::io::Read;
+use std::process;
+
+use forest::Forest;
+use render::Rgb;
+use sight::Direction;
+
+struct Grid {
+    width: usize,
+    height: usize,
+    // row by row
+    cells: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct GridError {
+    line: usize,
+    descr: &'static str,
+    input: String,
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} (input: {})", self.line, self.descr, self.input)
+    }
+}
 
-fn parse_line(line: &str) -> Vec<u8> {
+impl Grid {
+    fn parse(input: &str) -> Result<Grid, GridError> {
+        let mut g = Grid {
+            width: 0,
+            height: 0,
+            cells: Vec::new(),
+        };
+        for (i, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err = |descr| GridError {
+                line: i + 1,
+                descr,
+                input: String::from(line),
+            };
+            let row = parse_line(line).ok_or_else(|| err("not a digit"))?;
+            if g.height == 0 {
+                g.width = row.len();
+            } else if row.len() != g.width {
+                return Err(err("row length differs from the first row"));
+            }
+            g.cells.extend(row);
+            g.height += 1;
+        }
+        Ok(g)
+    }
+
+    fn get(&self, x: usize, y: usize) -> u8 {
+        self.cells[y * self.width + x]
+    }
+
+    // the cell one step away from `c`, if it is still inside the grid
+    fn step(&self, c: usize, (dx, dy): (isize, isize)) -> Option<usize> {
+        let x = (c % self.width).checked_add_signed(dx)?;
+        let y = (c / self.width).checked_add_signed(dy)?;
+        (x < self.width && y < self.height).then_some(y * self.width + x)
+    }
+}
+
+fn parse_line(line: &str) -> Option<Vec<u8>> {
     let mut r : Vec<u8> = Vec::new();
     for c in line.chars() {
-        r.push(c.to_digit(10).expect("parsing char") as u8);
+        r.push(c.to_digit(10)? as u8);
     }
-    r
+    Some(r)
 }
 
-fn visible_trees(field: &Vec<Vec<u8>>) -> usize {
-    let mut visible = Vec::new();
-    let end = field.len()-1;
-    for _ in 0..=end {
-        visible.push(vec![false; field.len()]);
+// One step in each direction, the four axes first and then the diagonals.
+// Viewing distances are indexed like this.
+const STEPS: [(isize, isize); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (-1, -1),
+    (1, -1),
+    (-1, 1),
+];
+
+const DIRECTION_NAMES: [&str; 8] = [
+    "right",
+    "left",
+    "down",
+    "up",
+    "down-right",
+    "up-left",
+    "up-right",
+    "down-left",
+];
+
+type Distances = [usize; 8];
+
+// Scores of eight directions don't fit into 64 bits on large grids, an 801x801
+// grid already reaches 400^8.
+type Score = u128;
+
+// the directions looked in, with or without the diagonals
+fn directions(diagonals: bool) -> &'static [(isize, isize)] {
+    if diagonals {
+        &STEPS
+    } else {
+        &STEPS[..4]
     }
-    for y in 0..=end {
-        let mut max = field[y][0];
-        visible[y][0] = true;
-        for x in 0..=end {
-            if field[y][x] > max {
-                max = field[y][x];
-                visible[y][x] = true;
-            }
-        }
+}
+
+// Every line of cells running in direction `step`, each starting at the edge
+// of the grid.
+fn lines(field: &Grid, step: (isize, isize)) -> Vec<Vec<usize>> {
+    let back = (-step.0, -step.1);
+    (0..field.cells.len())
+        .filter(|&start| field.step(start, back).is_none())
+        .map(|start| line_from(field, start, step))
+        .collect()
+}
 
-        max = field[y][end];
-        visible[y][end] = true;
-        for x in (0..=end).rev() {
-            if field[y][x] > max {
-                max = field[y][x];
-                visible[y][x] = true;
+fn line_from(field: &Grid, start: usize, step: (isize, isize)) -> Vec<usize> {
+    let mut line = vec![start];
+    let mut c = start;
+    while let Some(next) = field.step(c, step) {
+        line.push(next);
+        c = next;
+    }
+    line
+}
+
+// the line in direction `step` that passes through cell `c`, starting at the
+// edge
+fn line_through(field: &Grid, c: usize, step: (isize, isize)) -> Vec<usize> {
+    let mut start = c;
+    while let Some(prev) = field.step(start, (-step.0, -step.1)) {
+        start = prev;
+    }
+    line_from(field, start, step)
+}
+
+// which trees can be seen from outside the grid looking in one of `dirs`,
+// indexed like the cells
+fn visibility(field: &Grid, dirs: &[(isize, isize)]) -> Vec<bool> {
+    let mut visible = vec![false; field.cells.len()];
+    for &step in dirs {
+        for line in lines(field, step) {
+            let mut max = None;
+            for c in line {
+                if max.is_none_or(|m| field.cells[c] > m) {
+                    visible[c] = true;
+                    max = Some(field.cells[c]);
+                }
             }
         }
     }
+    visible
+}
+
+fn visible_trees(field: &Grid, dirs: &[(isize, isize)]) -> usize {
+    visibility(field, dirs).iter().filter(|v| **v).count()
+}
 
-    for x in 0..=end {
-        let mut max = field[0][x];
-        visible[0][x] = true;
-        for y in 0..=end {
-            if field[y][x] > max {
-                max = field[y][x];
-                visible[y][x] = true;
+// Walks along a line of cells, each looking back towards the start of the
+// line. The stack holds the trees that can still block the view of a later
+// tree: every tree lower than the current one is hidden behind it and dropped,
+// so the top of the stack is the first tree at least as high.
+fn sweep(field: &Grid, cells: &[usize], dir: usize, dist: &mut [Distances]) {
+    let mut stack: Vec<usize> = Vec::new();
+    for (i, &c) in cells.iter().enumerate() {
+        let h = field.cells[c];
+        while let Some(&j) = stack.last() {
+            if field.cells[cells[j]] >= h {
+                break;
             }
+            stack.pop();
         }
+        dist[c][dir] = match stack.last() {
+            Some(&j) => i - j,
+            None => i,
+        };
+        stack.push(i);
+    }
+}
 
-        max = field[end][x];
-        visible[end][x] = true;
-        for y in (0..=end).rev() {
-            if field[y][x] > max {
-                max = field[y][x];
-                visible[y][x] = true;
-            }
+// viewing distance of every tree in each of `dirs`, indexed like the cells
+fn viewing_distances(field: &Grid, dirs: &[(isize, isize)]) -> Vec<Distances> {
+    let mut dist = vec![[0; 8]; field.cells.len()];
+    for (dir, &(dx, dy)) in dirs.iter().enumerate() {
+        // looking one way is looking back while walking the other way
+        for line in lines(field, (-dx, -dy)) {
+            sweep(field, &line, dir, &mut dist);
         }
     }
+    dist
+}
 
-    visible.iter().fold(0, |count, row|
-        count + row.iter().fold(0, |rowcount, elem|
-            if *elem {
-                rowcount+1
-            } else {
-                rowcount
-            }
-        )
-    )
+// product of the viewing distances in the first `n` directions
+fn score(d: &Distances, n: usize) -> Score {
+    d[..n].iter().map(|&d| d as Score).product()
+}
+
+fn scores(field: &Grid, dirs: &[(isize, isize)]) -> Vec<Score> {
+    viewing_distances(field, dirs)
+        .iter()
+        .map(|d| score(d, dirs.len()))
+        .collect()
+}
+
+fn max_score(field: &Grid, dirs: &[(isize, isize)]) -> Score {
+    scores(field, dirs).into_iter().max().unwrap_or(0)
+}
+
+// cell with the highest score, the first one on ties
+fn best_spot(scores: &[Score]) -> Option<usize> {
+    (0..scores.len()).max_by_key(|&i| (scores[i], Reverse(i)))
 }
 
-fn tree_score(field: &Vec<Vec<u8>>, tx: usize, ty: usize) -> usize {
-    let h = field[ty][tx];
-    let end = field.len()-1;
+struct SiteOptions {
+    count: usize,
+    no_border: bool,
+    min_distance: usize,
+    min_height: u8,
+}
 
-    let mut xscore = 0;
-    for x in tx+1..=end {
-        xscore += 1;
-        if field[ty][x] >= h {
+// The best sites for tree houses, best first. Sites are picked greedily by
+// score, skipping trees that break a constraint or are closer than the
+// minimum (euclidean) distance to a site picked before.
+fn top_sites(field: &Grid, scores: &[Score], opts: &SiteOptions) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by_key(|&c| (Reverse(scores[c]), c));
+    let xy = |c: usize| ((c % field.width) as isize, (c / field.width) as isize);
+    let on_border = |c: usize| {
+        let (x, y) = xy(c);
+        x == 0 || y == 0 || x as usize == field.width - 1 || y as usize == field.height - 1
+    };
+    let min_sq = (opts.min_distance * opts.min_distance) as isize;
+    let mut sites: Vec<usize> = Vec::new();
+    for c in order {
+        if sites.len() == opts.count {
             break;
         }
+        if field.cells[c] < opts.min_height || (opts.no_border && on_border(c)) {
+            continue;
+        }
+        let (x, y) = xy(c);
+        let too_close = sites.iter().any(|&s| {
+            let (sx, sy) = xy(s);
+            (sx - x) * (sx - x) + (sy - y) * (sy - y) < min_sq
+        });
+        if !too_close {
+            sites.push(c);
+        }
     }
+    sites
+}
 
-    let mut x2score = 0;
-    for x in (0..tx).rev() {
-        x2score += 1;
-        if field[ty][x] >= h {
-            break;
+// The trees that end the view from cell `c`. Where the view reaches the edge
+// of the grid nothing limits it.
+fn blockers(field: &Grid, dist: &Distances, c: usize, dirs: &[(isize, isize)]) -> Vec<usize> {
+    let (x, y) = ((c % field.width) as isize, (c / field.width) as isize);
+    let mut r = Vec::new();
+    for (dir, &(dx, dy)) in dirs.iter().enumerate() {
+        let d = dist[dir] as isize;
+        if d == 0 {
+            continue;
+        }
+        let b = (y + dy * d) as usize * field.width + (x + dx * d) as usize;
+        if field.cells[b] >= field.cells[c] {
+            r.push(b);
         }
     }
+    r
+}
 
-    let mut yscore = 0;
-    for y in ty+1..=end {
-        yscore += 1;
-        if field[y][tx] >= h {
-            break;
+#[derive(Clone, Copy)]
+enum Layer {
+    Visible,
+    Score,
+}
+
+struct Picture {
+    greys: Vec<u8>,
+    colors: Vec<Rgb>,
+    best: Option<(usize, Score)>,
+    blockers: Vec<usize>,
+}
+
+const BEST_COLOR: Rgb = [255, 255, 255];
+const BLOCKER_COLOR: Rgb = [0, 255, 255];
+
+// Greyscale and color values of every tree for one layer. The greyscale has
+// no room for highlights, the colors mark the best spot and the trees that
+// limit its view.
+fn picture(field: &Grid, layer: Layer, dirs: &[(isize, isize)]) -> Picture {
+    let dist = viewing_distances(field, dirs);
+    let scores: Vec<Score> = dist.iter().map(|d| score(d, dirs.len())).collect();
+    let (greys, mut colors): (Vec<u8>, Vec<Rgb>) = match layer {
+        Layer::Visible => visibility(field, dirs)
+            .iter()
+            .zip(&field.cells)
+            .map(|(&v, &h)| {
+                if v {
+                    (255, [0, 80 + 17 * h, 0])
+                } else {
+                    (0, [40 + 10 * h; 3])
+                }
+            })
+            .unzip(),
+        Layer::Score => {
+            // scores span several orders of magnitude, so they are shown on
+            // a log scale
+            let max = scores.iter().max().copied().unwrap_or(0);
+            scores
+                .iter()
+                .map(|&s| {
+                    let t = if max == 0 {
+                        0.0
+                    } else {
+                        ((1 + s) as f64).ln() / ((1 + max) as f64).ln()
+                    };
+                    ((t * 255.0).round() as u8, render::ramp(t))
+                })
+                .unzip()
         }
+    };
+    let best = best_spot(&scores);
+    let blockers = best.map_or(Vec::new(), |c| blockers(field, &dist[c], c, dirs));
+    for &b in &blockers {
+        colors[b] = BLOCKER_COLOR;
+    }
+    if let Some(c) = best {
+        colors[c] = BEST_COLOR;
+    }
+    Picture {
+        greys,
+        colors,
+        best: best.map(|c| (c, scores[c])),
+        blockers,
     }
+}
 
-    let mut y2score = 0;
-    for y in (0..ty).rev() {
-        y2score += 1;
-        if field[y][tx] >= h {
-            break;
+fn layer_arg(arg: Option<&String>) -> Layer {
+    match arg.map(|s| s.as_str()) {
+        Some("visible") => Layer::Visible,
+        Some("score") => Layer::Score,
+        _ => panic!("expected visible or score"),
+    }
+}
+
+fn read_grid() -> Grid {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input).expect("failed to read stdin");
+    match Grid::parse(&input) {
+        Ok(g) => g,
+        Err(e) => {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+    }
+}
+
+// Writes a .pgm or .ppm image of a layer, chosen by the file extension.
+fn image(field: &Grid, flags: &[String]) {
+    let layer = layer_arg(flags.first());
+    let mut scale = 1;
+    let mut diagonals = false;
+    let mut path = None;
+    let mut it = flags.iter().skip(1);
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--scale" => {
+                let v = it.next().expect("--scale needs a value");
+                scale = v.parse().expect("failed to parse --scale");
+            }
+            "--diagonals" => diagonals = true,
+            _ if path.is_none() => path = Some(arg),
+            _ => panic!("unknown argument {arg}"),
+        }
+    }
+    let path = path.expect("image needs an output file");
+    let p = picture(field, layer, directions(diagonals));
+    let data = if path.ends_with(".pgm") {
+        render::pgm(field.width, field.height, &p.greys, scale)
+    } else if path.ends_with(".ppm") {
+        render::ppm(field.width, field.height, &p.colors, scale)
+    } else {
+        eprintln!("error: {path}: output must be a .pgm or .ppm file");
+        process::exit(1);
+    };
+    if let Err(e) = fs::write(path, data) {
+        eprintln!("error: {path}: {e}");
+        process::exit(1);
+    }
+}
+
+fn show(field: &Grid, flags: &[String]) {
+    let diagonals = match flags.get(1).map(|s| s.as_str()) {
+        None => false,
+        Some("--diagonals") if flags.len() == 2 => true,
+        Some(_) => panic!("unknown argument {}", flags[flags.len() - 1]),
+    };
+    let p = picture(field, layer_arg(flags.first()), directions(diagonals));
+    print!("{}", render::ansi(field.width, field.height, &field.cells, &p.colors));
+    if let Some((c, score)) = p.best {
+        println!(
+            "best spot {},{} with score {} (white), view limited by {} trees (cyan)",
+            c % field.width,
+            c / field.width,
+            score,
+            p.blockers.len()
+        );
+    }
+}
+
+fn sites(field: &Grid, flags: &[String]) {
+    let mut opts = SiteOptions {
+        count: 10,
+        no_border: false,
+        min_distance: 0,
+        min_height: 0,
+    };
+    let mut diagonals = false;
+    let mut it = flags.iter();
+    while let Some(arg) = it.next() {
+        let mut value = || {
+            it.next()
+                .unwrap_or_else(|| panic!("{arg} needs a value"))
+                .parse::<usize>()
+                .unwrap_or_else(|_| panic!("failed to parse {arg}"))
+        };
+        match arg.as_str() {
+            "-k" | "--count" => opts.count = value(),
+            "--min-distance" => opts.min_distance = value(),
+            "--min-height" => opts.min_height = value().min(10) as u8,
+            "--no-border" => opts.no_border = true,
+            "--diagonals" => diagonals = true,
+            _ => panic!("unknown argument {arg}"),
+        }
+    }
+
+    let dirs = directions(diagonals);
+    let dist = viewing_distances(field, dirs);
+    let scores: Vec<Score> = dist.iter().map(|d| score(d, dirs.len())).collect();
+    let sites = top_sites(field, &scores, &opts);
+    if sites.is_empty() {
+        eprintln!("error: no tree satisfies the constraints");
+        process::exit(1);
+    }
+    // eight directions give scores far wider than the other columns
+    let w = sites.iter().map(|&c| scores[c].to_string().len()).max().unwrap_or(0).max(12);
+    print!("{:>4}  {:>5}  {:>5}  {:>6}  {:>w$}", "rank", "x", "y", "height", "score");
+    for name in &DIRECTION_NAMES[..dirs.len()] {
+        print!("  {:>10}", name);
+    }
+    println!();
+    for (rank, &c) in sites.iter().enumerate() {
+        print!(
+            "{:>4}  {:>5}  {:>5}  {:>6}  {:>w$}",
+            rank + 1,
+            c % field.width,
+            c / field.width,
+            field.cells[c],
+            scores[c]
+        );
+        for d in &dist[c][..dirs.len()] {
+            print!("  {:>10}", d);
+        }
+        println!();
+    }
+    if sites.len() < opts.count {
+        eprintln!("warning: only {} trees satisfy the constraints", sites.len());
+    }
+}
+
+// Applies a batch of height changes, printing the answers after each.
+fn update(field: Grid, flags: &[String]) {
+    let mut diagonals = false;
+    let mut path = None;
+    for arg in flags {
+        match arg.as_str() {
+            "--diagonals" => diagonals = true,
+            _ if path.is_none() => path = Some(arg),
+            _ => panic!("unknown argument {arg}"),
         }
     }
-    
-    xscore * x2score * yscore * y2score
+    let path = path.expect("update needs a batch file");
+    let input = fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("error: {path}: {e}");
+        process::exit(1);
+    });
+    let updates = match forest::parse_updates(&input, &field) {
+        Ok(u) => u,
+        Err(e) => {
+            eprintln!("error: {path}: {e}");
+            process::exit(1);
+        }
+    };
+    let mut forest = Forest::new(field, directions(diagonals));
+    println!("start: {} visible, max score {}", forest.visible(), forest.max_score());
+    for u in updates {
+        forest.set_height(u.x, u.y, u.height);
+        println!(
+            "{},{} -> {}: {} visible, max score {}",
+            u.x,
+            u.y,
+            u.height,
+            forest.visible(),
+            forest.max_score()
+        );
+    }
 }
 
-fn max_score(field: &Vec<Vec<u8>>) -> usize {
-    let mut max = 0;
-    for x in 0..field.len() {
-        for y in 0..field.len() {
-            let score = tree_score(field, x, y);
-            if score > max {
-                max = score;
+// Looks from one tree along the given lines of sight, the axes (and
+// diagonals) by default.
+fn sight(field: &Grid, flags: &[String]) {
+    let coord = |i: usize, name: &str, max: usize| -> usize {
+        let v: usize = flags
+            .get(i)
+            .unwrap_or_else(|| panic!("sight needs X and Y"))
+            .parse()
+            .unwrap_or_else(|_| panic!("failed to parse {name}"));
+        if v >= max {
+            eprintln!("error: {name} {v} is outside the grid");
+            process::exit(1);
+        }
+        v
+    };
+    let x = coord(0, "X", field.width);
+    let y = coord(1, "Y", field.height);
+    let mut diagonals = false;
+    let mut dirs = Vec::new();
+    let mut it = flags.iter().skip(2);
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--diagonals" => diagonals = true,
+            "--dir" => {
+                let v = it.next().expect("--dir needs a value");
+                match Direction::parse(v) {
+                    Some(d) => dirs.push(d),
+                    None => {
+                        eprintln!("error: invalid direction {v}, expected DX,DY");
+                        process::exit(1);
+                    }
+                }
             }
-            //dbg!(x, y, score);
+            _ => panic!("unknown argument {arg}"),
+        }
+    }
+    if dirs.is_empty() || diagonals {
+        dirs.extend(directions(diagonals).iter().map(|&(dx, dy)| Direction { dx, dy }));
+    }
+
+    let c = y * field.width + x;
+    let mut visible = vec![false; field.cells.len()];
+    let mut score: Option<Score> = Some(1);
+    println!("tree {},{} with height {}", x, y, field.get(x, y));
+    println!("{:>9}  {:>8}  {:>7}", "direction", "distance", "visible");
+    for d in dirs {
+        let s = sight::look(field, c, d);
+        println!(
+            "{:>9}  {:>8}  {:>7}",
+            format!("{},{}", d.dx, d.dy),
+            s.distance,
+            s.visible.len()
+        );
+        for t in s.visible {
+            visible[t] = true;
+        }
+        score = score.and_then(|p| p.checked_mul(s.distance as Score));
+    }
+    println!("visible trees: {}", visible.iter().filter(|v| **v).count());
+    match score {
+        Some(score) => println!("score: {}", score),
+        None => {
+            eprintln!("error: score overflows");
+            process::exit(1);
         }
     }
-    max
 }
 
 fn main() {
-    let mut field = Vec::new();
-    
-    loop {
-        let mut line = String::new();
-
-        let bytes = io::stdin()
-            .read_line(&mut line)
-            .expect("Failed to read line");
-            
-        if bytes == 0 {
-            break;
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (cmd, flags) = match args.first() {
+        Some(a) if !a.starts_with('-') => (Some(a.as_str()), &args[1..]),
+        _ => (None, &args[..]),
+    };
+    match cmd {
+        None => {}
+        Some("image") => {
+            image(&read_grid(), flags);
+            return;
+        }
+        Some("show") => {
+            show(&read_grid(), flags);
+            return;
+        }
+        Some("update") => {
+            update(read_grid(), flags);
+            return;
+        }
+        Some("sites") => {
+            sites(&read_grid(), flags);
+            return;
+        }
+        Some("sight") => {
+            sight(&read_grid(), flags);
+            return;
+        }
+        Some(cmd) => panic!("unknown command {cmd}"),
+    }
+
+    let mut diagonals = false;
+    for arg in flags {
+        match arg.as_str() {
+            "--diagonals" => diagonals = true,
+            _ => panic!("unknown argument {arg}"),
+        }
+    }
+
+    let field = read_grid();
+    let dirs = directions(diagonals);
+    println!("{}", visible_trees(&field, dirs));
+    println!("{}", max_score(&field, dirs));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // xorshift64, good enough for random test grids
+    pub struct Rng(pub u64);
+
+    impl Rng {
+        pub fn next(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    pub fn random_grid(rng: &mut Rng) -> Grid {
+        let width = 1 + rng.next(30);
+        let height = 1 + rng.next(30);
+        // few heights make ties, which are easy to get wrong, more likely
+        let heights = 1 + rng.next(10);
+        Grid {
+            width,
+            height,
+            cells: (0..width * height).map(|_| rng.next(heights) as u8).collect(),
+        }
+    }
+
+    // the original scan outwards from a single tree, kept to check the sweeps
+    // against
+    fn tree_score(field: &Grid, tx: usize, ty: usize, dirs: &[(isize, isize)]) -> Score {
+        let c = ty * field.width + tx;
+        let h = field.get(tx, ty);
+        let mut score = 1;
+        for &step in dirs {
+            let mut d = 0;
+            let mut p = c;
+            while let Some(next) = field.step(p, step) {
+                d += 1;
+                if field.cells[next] >= h {
+                    break;
+                }
+                p = next;
+            }
+            score *= d as Score;
         }
+        score
+    }
 
-        let row = parse_line(line.trim());
-        field.push(row);
+    #[test]
+    fn eight_direction_score_beyond_64_bits() {
+        let n = 601;
+        let mut cells = vec![0; n * n];
+        cells[n * n / 2] = 9;
+        let field = Grid {
+            width: n,
+            height: n,
+            cells,
+        };
+        assert_eq!(max_score(&field, directions(true)), 300u128.pow(8));
     }
 
-    let score = max_score(&field);
-    println!("{score}");
+    // Compares the sweeps with scanning from every tree on random grids.
+    #[test]
+    fn sweeps_match_scans() {
+        let n = 100;
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        for i in 0..n {
+            let field = random_grid(&mut rng);
+            let (width, height) = (field.width, field.height);
+            for dirs in [directions(false), directions(true)] {
+                let fast = scores(&field, dirs);
+                for y in 0..height {
+                    for x in 0..width {
+                        let slow = tree_score(&field, x, y, dirs);
+                        if fast[y * width + x] != slow {
+                            panic!(
+                                "grid {i} ({width}x{height}): score at {x},{y} in {} directions is {} instead of {slow}",
+                                dirs.len(),
+                                fast[y * width + x]
+                            );
+                        }
+                    }
+                }
+            }
+            // along the axes and diagonals the line of sight has to agree with the
+            // sweeps
+            let dist = viewing_distances(&field, &STEPS);
+            for (c, d) in dist.iter().enumerate() {
+                for (dir, &(dx, dy)) in STEPS.iter().enumerate() {
+                    let s = sight::look(&field, c, Direction { dx, dy });
+                    if s.distance != d[dir] {
+                        panic!(
+                            "grid {i} ({width}x{height}): line of sight {dx},{dy} from {},{} reaches {} instead of {}",
+                            c % width,
+                            c / width,
+                            s.distance,
+                            d[dir]
+                        );
+                    }
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,67 @@
+pub type Rgb = [u8; 3];
+
+// Binary greyscale netpbm image with one `scale` x `scale` square per tree.
+pub fn pgm(width: usize, height: usize, greys: &[u8], scale: usize) -> Vec<u8> {
+    let mut out = format!("P5\n{} {}\n255\n", width * scale, height * scale).into_bytes();
+    for y in 0..height {
+        for _ in 0..scale {
+            for &g in &greys[y * width..(y + 1) * width] {
+                out.extend(std::iter::repeat_n(g, scale));
+            }
+        }
+    }
+    out
+}
+
+// Binary color netpbm image, laid out like `pgm`.
+pub fn ppm(width: usize, height: usize, colors: &[Rgb], scale: usize) -> Vec<u8> {
+    let mut out = format!("P6\n{} {}\n255\n", width * scale, height * scale).into_bytes();
+    for y in 0..height {
+        for _ in 0..scale {
+            for c in &colors[y * width..(y + 1) * width] {
+                for _ in 0..scale {
+                    out.extend_from_slice(c);
+                }
+            }
+        }
+    }
+    out
+}
+
+fn luminance(c: Rgb) -> u32 {
+    (299 * c[0] as u32 + 587 * c[1] as u32 + 114 * c[2] as u32) / 1000
+}
+
+// Prints the tree heights on 24-bit background colors, with black or white
+// digits depending on how light the background is.
+pub fn ansi(width: usize, height: usize, heights: &[u8], colors: &[Rgb]) -> String {
+    let mut out = String::new();
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let c = colors[i];
+            let fg = if luminance(c) > 128 { 30 } else { 97 };
+            out.push_str(&format!(
+                "\x1b[48;2;{};{};{}m\x1b[{}m{}",
+                c[0], c[1], c[2], fg, heights[i]
+            ));
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+// Heatmap colors from black over purple and orange to yellow, for t between
+// 0 and 1.
+pub fn ramp(t: f64) -> Rgb {
+    const STOPS: [Rgb; 4] = [[0, 0, 0], [128, 0, 128], [255, 128, 0], [255, 255, 0]];
+    let t = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f64;
+    let i = (t as usize).min(STOPS.len() - 2);
+    let f = t - i as f64;
+    let mut c = [0; 3];
+    for k in 0..3 {
+        let (a, b) = (STOPS[i][k] as f64, STOPS[i + 1][k] as f64);
+        c[k] = (a + (b - a) * f).round() as u8;
+    }
+    c
+}
@@ -0,0 +1,109 @@
+use crate::Grid;
+
+// A line of sight with integer components, walked in steps of one along its
+// longer component.
+#[derive(Clone, Copy)]
+pub struct Direction {
+    pub dx: isize,
+    pub dy: isize,
+}
+
+fn gcd(a: isize, b: isize) -> isize {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl Direction {
+    // Parses `DX,DY`, reduced so that 2,4 and 1,2 are the same line.
+    pub fn parse(s: &str) -> Option<Direction> {
+        let (dx, dy) = s.split_once(',')?;
+        let (dx, dy): (isize, isize) = (dx.trim().parse().ok()?, dy.trim().parse().ok()?);
+        let g = gcd(dx, dy);
+        if g == 0 {
+            return None;
+        }
+        Some(Direction {
+            dx: dx / g,
+            dy: dy / g,
+        })
+    }
+
+    fn major(self) -> isize {
+        self.dx.abs().max(self.dy.abs())
+    }
+}
+
+pub struct Sight {
+    // steps until the first point at least as high as the observer's tree,
+    // or until the edge
+    pub distance: usize,
+    // trees on the line the observer can see over everything in front
+    pub visible: Vec<usize>,
+}
+
+// Heights of the points along the line from cell `c`, one per step, up to the
+// last point between cells of the grid. A point between two cells gets the
+// linear interpolation of their heights. Heights are scaled by the longer
+// component so they stay integers; points exactly on a tree come with its
+// cell.
+fn samples(field: &Grid, c: usize, d: Direction) -> Vec<(i64, Option<usize>)> {
+    let m = d.major();
+    let (x, y) = ((c % field.width) as isize, (c / field.width) as isize);
+    let cell = |x: isize, y: isize| {
+        let inside = (0..field.width as isize).contains(&x) && (0..field.height as isize).contains(&y);
+        inside.then(|| y as usize * field.width + x as usize)
+    };
+    let h = |c: usize| field.cells[c] as i64;
+    let mut r = Vec::new();
+    for k in 1.. {
+        // position in units of 1/m; only one of the coordinates can fall
+        // between two cells
+        let (px, py) = (x * m + k * d.dx, y * m + k * d.dy);
+        let (fx, rx) = (px.div_euclid(m), px.rem_euclid(m));
+        let (fy, ry) = (py.div_euclid(m), py.rem_euclid(m));
+        let Some(a) = cell(fx, fy) else {
+            break;
+        };
+        let sample = if rx != 0 {
+            cell(fx + 1, fy).map(|b| (h(a) * (m - rx) as i64 + h(b) * rx as i64, None))
+        } else if ry != 0 {
+            cell(fx, fy + 1).map(|b| (h(a) * (m - ry) as i64 + h(b) * ry as i64, None))
+        } else {
+            Some((h(a) * m as i64, Some(a)))
+        };
+        match sample {
+            Some(s) => r.push(s),
+            None => break,
+        }
+    }
+    r
+}
+
+// Looks from the top of the tree in cell `c` along `d`. For the axes and
+// diagonals the distance is the usual viewing distance. A tree is visible
+// when the slope up to it is steeper than to every point in front of it,
+// interpolated points included.
+pub fn look(field: &Grid, c: usize, d: Direction) -> Sight {
+    let eye = field.cells[c] as i64 * d.major() as i64;
+    let points = samples(field, c, d);
+    let distance = points
+        .iter()
+        .position(|&(h, _)| h >= eye)
+        .map_or(points.len(), |i| i + 1);
+    let mut visible = Vec::new();
+    // steepest point so far as rise and number of steps
+    let mut steepest: Option<(i64, i64)> = None;
+    for (i, &(h, tree)) in points.iter().enumerate() {
+        let (rise, run) = (h - eye, i as i64 + 1);
+        if steepest.is_none_or(|(r, k)| rise * k > r * run) {
+            steepest = Some((rise, run));
+            if let Some(t) = tree {
+                visible.push(t);
+            }
+        }
+    }
+    Sight { distance, visible }
+}